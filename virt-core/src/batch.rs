@@ -0,0 +1,213 @@
+use std::mem;
+
+use crate::render::{Renderer, BlendMode};
+use crate::buffer::{Buffer, BatchBuffer, ColoredVertex, TexturedBuffer, TexturedVertex};
+use crate::frame::Frame;
+use crate::gradient::{Gradient, GradientSpan};
+use crate::vector::Vector;
+use crate::transform::Transform;
+use crate::color::Color;
+use crate::error::Result;
+
+/// Batch is a run of solid fills packed into one mesh, with the color in every vertex.
+#[derive(Debug, Clone, Default)]
+pub struct Batch {
+    pub vertices: Vec<ColoredVertex>,
+    pub indices: Vec<u32>,
+
+    pub buffer: Option<BatchBuffer>,
+}
+
+/// Batcher sits between the shapes and a Renderer, collecting consecutive solid fills into
+/// batches drawn with a single call each. Anything else, rounded rectangles, textures and
+/// gradients, ends the current batch so the drawing order is kept, and so does a fill
+/// blended differently from the ones collected.
+///
+/// The batches are kept between frames and only uploaded again when their geometry or
+/// colors changed.
+pub struct Batcher<'a> {
+    pub renderer: &'a mut dyn Renderer,
+    pub batches: &'a mut Vec<Batch>,
+
+    // Index of the batch being collected
+    current: usize,
+
+    // Baked into the batched vertices, and passed on for everything else
+    transform: Transform,
+
+    // Set by blend, and the mode of the fills collected so far
+    blend: BlendMode,
+    batch_blend: BlendMode,
+
+    vertices: Vec<ColoredVertex>,
+    indices: Vec<u32>,
+}
+
+impl<'a> Batcher<'a> {
+    pub fn new(renderer: &'a mut dyn Renderer, batches: &'a mut Vec<Batch>) -> Batcher<'a> {
+        Batcher {
+            renderer,
+            batches,
+            current: 0,
+            transform: Transform::identity(),
+            blend: BlendMode::Normal,
+            batch_blend: BlendMode::Normal,
+            vertices: Vec::new(),
+            indices: Vec::new(),
+        }
+    }
+
+    // Draws the fills collected so far as one batch
+    fn flush(&mut self) -> Result<()> {
+        if self.indices.is_empty() {
+            self.vertices.clear();
+            return Ok(());
+        }
+
+        if self.current == self.batches.len() {
+            self.batches.push(Batch::default());
+        }
+
+        let batch = &mut self.batches[self.current];
+
+        if batch.vertices != self.vertices || batch.indices != self.indices {
+            batch.vertices = mem::replace(&mut self.vertices, Vec::new());
+            batch.indices = mem::replace(&mut self.indices, Vec::new());
+            batch.buffer = None;
+        } else {
+            self.vertices.clear();
+            self.indices.clear();
+        }
+
+        self.current += 1;
+
+        // The vertices are already in widget space
+        self.renderer.transform(Transform::identity());
+        self.renderer.blend(self.batch_blend);
+        self.renderer.batch(&mut batch.buffer, &batch.vertices, &batch.indices)
+    }
+}
+
+impl<'a> Renderer for Batcher<'a> {
+    fn resolution(&self) -> Vector {
+        self.renderer.resolution()
+    }
+
+    fn begin(&mut self, clear: Color) -> Result<()> {
+        self.current = 0;
+        self.vertices.clear();
+        self.indices.clear();
+        self.blend = BlendMode::Normal;
+        self.batch_blend = BlendMode::Normal;
+
+        self.renderer.begin(clear)
+    }
+
+    fn transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    fn scale(&self) -> f32 {
+        self.transform.norm()
+    }
+
+    fn blend(&mut self, mode: BlendMode) {
+        self.blend = mode;
+    }
+
+    fn fill(&mut self,
+        _buffer: &mut Option<Buffer>,
+        vertices: &[Vector],
+        indices: &[u32],
+        color: Color,
+    ) -> Result<()> {
+        if self.blend != self.batch_blend {
+            self.flush()?;
+            self.batch_blend = self.blend;
+        }
+
+        let col = color.to_premultiplied_linear();
+        let transform = self.transform;
+        let first = self.vertices.len() as u32;
+
+        self.vertices.extend(vertices.iter().map(|&v| ColoredVertex::new(transform.apply(v).position, col)));
+        self.indices.extend(indices.iter().map(|&i| first + i));
+
+        Ok(())
+    }
+
+    fn rounded_rect(&mut self,
+        buffer: &mut Option<Buffer>,
+        position: Vector,
+        size: Vector,
+        radius: [f32; 4],
+        color: Color,
+    ) -> Result<()> {
+        self.flush()?;
+        self.renderer.transform(self.transform);
+        self.renderer.blend(self.blend);
+        self.renderer.rounded_rect(buffer, position, size, radius, color)
+    }
+
+    fn textured(&mut self,
+        buffer: &mut Option<TexturedBuffer>,
+        vertices: &[TexturedVertex],
+        indices: &[u32],
+        image: &Frame,
+        repeat: bool,
+        color: Color,
+    ) -> Result<()> {
+        self.flush()?;
+        self.renderer.transform(self.transform);
+        self.renderer.blend(self.blend);
+        self.renderer.textured(buffer, vertices, indices, image, repeat, color)
+    }
+
+    fn gradient(&mut self,
+        buffer: &mut Option<TexturedBuffer<Vector>>,
+        vertices: &[Vector],
+        indices: &[u32],
+        gradient: &Gradient,
+        span: GradientSpan,
+    ) -> Result<()> {
+        self.flush()?;
+        self.renderer.transform(self.transform);
+        self.renderer.blend(self.blend);
+        self.renderer.gradient(buffer, vertices, indices, gradient, span)
+    }
+
+    fn batch(&mut self,
+        buffer: &mut Option<BatchBuffer>,
+        vertices: &[ColoredVertex],
+        indices: &[u32],
+    ) -> Result<()> {
+        self.flush()?;
+        self.renderer.transform(self.transform);
+        self.renderer.blend(self.blend);
+        self.renderer.batch(buffer, vertices, indices)
+    }
+
+    fn push_clip(&mut self,
+        buffer: &mut Option<Buffer>,
+        vertices: &[Vector],
+        indices: &[u32],
+    ) -> Result<()> {
+        self.flush()?;
+        self.renderer.transform(self.transform);
+        self.renderer.push_clip(buffer, vertices, indices)
+    }
+
+    fn pop_clip(&mut self) -> Result<()> {
+        self.flush()?;
+        self.renderer.pop_clip()
+    }
+
+    fn end(&mut self) -> Result<()> {
+        self.flush()?;
+
+        // Drop the batches of a longer previous frame
+        self.batches.truncate(self.current);
+
+        self.renderer.end()
+    }
+}
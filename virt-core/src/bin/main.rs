@@ -12,8 +12,6 @@ fn main() {
     let (mut core_state, event_loop) = CoreState::new().unwrap();
 
     event_loop.run(move |event, _, control_flow| {
-        //*control_flow = ControlFlow::Poll;
-
         match event {
             Event::WindowEvent {
                 event: WindowEvent::CloseRequested,
@@ -30,7 +28,7 @@ fn main() {
                 core_state.surfaces
                     .get_mut(&window_id)
                     .unwrap()
-                    .recreate_swapchain = true;
+                    .resized();
             }
             Event::WindowEvent {
                 window_id,
@@ -43,19 +41,30 @@ fn main() {
             } => {
                 let surface = core_state.surfaces.get_mut(&window_id).unwrap();
 
-                //let position = position.to_logical::<f64>(1f64);
+                // Shapes are laid out in logical pixels
+                let position = position.to_logical::<f32>(surface.window().scale_factor());
 
                 match surface.cur_mouse_pos {
                     Some(val) => {
                         surface.las_mouse_pos = Some(val);
-                        surface.cur_mouse_pos = Some(Vector::new(position.x as f32, position.y as f32));
+                        surface.cur_mouse_pos = Some(Vector::new(position.x, position.y));
                     },
                     None => {
-                        surface.cur_mouse_pos = Some(Vector::new(position.x as f32, position.y as f32));
+                        surface.cur_mouse_pos = Some(Vector::new(position.x, position.y));
                     }
                 }
 
-                
+                surface.widget.hover(surface.cur_mouse_pos);
+            }
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::CursorLeft { .. },
+                ..
+            } => {
+                let surface = core_state.surfaces.get_mut(&window_id).unwrap();
+
+                surface.las_mouse_pos = surface.cur_mouse_pos.take();
+                surface.widget.hover(None);
             }
             Event::WindowEvent {
                 window_id,
@@ -72,18 +81,20 @@ fn main() {
 
                     match surface.cur_mouse_pos {
                         Some(val) => {
-                            for button in &mut surface.widget.buttons {
-                                button.clicked(val.unproject(surface.widget.bound)).unwrap();
-                            };
+                            surface.widget.click(val).unwrap();
                         },
                         None => {},
                     }
                 };
             }
+            // Only surfaces that changed are drawn, the loop sleeps until the next event otherwise
+            Event::MainEventsCleared => {
+                core_state.request_redraws();
+            }
             Event::RedrawEventsCleared => {
-                core_state.surfaces
-                    .values()
-                    .for_each(|s| s.surface.window().request_redraw());
+                if *control_flow != ControlFlow::Exit {
+                    *control_flow = core_state.control_flow();
+                }
             }
             Event::RedrawRequested(window_id) => {
                 core_state.draw(window_id).unwrap();
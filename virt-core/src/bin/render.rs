@@ -0,0 +1,49 @@
+use virt_core::headless::HeadlessState;
+use virt_core::render::Backend;
+use virt_core::util::CoreConfig;
+use virt_core::decoder;
+
+use std::env;
+use std::process;
+
+// Renders a single widget config to a png without opening a window. The device and the MSAA
+// come from config.toml in the working directory when there is one, --msaa overrides it.
+//
+// usage: render [--software] [--msaa=<samples>] <widget.toml> <output.png>
+fn main() {
+    let mut backend = Backend::Vulkan;
+    let mut samples = None;
+    let mut paths = Vec::new();
+
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--software" => backend = Backend::Software,
+            a if a.starts_with("--msaa=") => match a["--msaa=".len()..].parse() {
+                Ok(n) => samples = Some(n),
+                Err(_) => usage(),
+            },
+            _ => paths.push(arg),
+        }
+    }
+
+    if paths.len() != 2 {
+        usage();
+    }
+
+    let config = CoreConfig::new().ok();
+    let samples = samples.or_else(|| config.as_ref().and_then(|c| c.msaa)).unwrap_or(1);
+    let selector = config.as_ref().and_then(|c| c.device.as_ref());
+
+    let headless = HeadlessState::with_msaa(backend, samples, selector).unwrap();
+
+    let widget_config = decoder::decode(&paths[0]).unwrap();
+
+    let frame = headless.render(widget_config).unwrap();
+
+    frame.save_png(&paths[1]).unwrap();
+}
+
+fn usage() -> ! {
+    eprintln!("usage: render [--software] [--msaa=<samples>] <widget.toml> <output.png>");
+    process::exit(1);
+}
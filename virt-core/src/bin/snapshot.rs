@@ -0,0 +1,67 @@
+use virt_core::headless::HeadlessState;
+use virt_core::snapshot::{Snapshots, SnapshotStatus, FIXTURES};
+use virt_core::render::Backend;
+use virt_core::util::CoreConfig;
+
+use std::env;
+use std::process;
+
+// Renders every widget config under the fixtures directory and compares it against
+// the reference png stored next to it, writing <name>.diff.png on failure. Rendered on the
+// device and with the MSAA of config.toml in the working directory when there is one.
+//
+// usage: snapshot [fixtures dir] [--bless] [--software]
+// The fixtures dir defaults to the one of this crate.
+fn main() {
+    let mut root = String::from(FIXTURES);
+    let mut bless = false;
+    let mut backend = Backend::Vulkan;
+
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--bless" => bless = true,
+            "--software" => backend = Backend::Software,
+            _ => root = arg,
+        }
+    }
+
+    let headless = match CoreConfig::new() {
+        Ok(config) => HeadlessState::from_config(backend, &config),
+        Err(_) => HeadlessState::new(backend),
+    }
+    .unwrap();
+
+    let mut snapshots = Snapshots::new(&root);
+    snapshots.bless = bless;
+
+    let results = snapshots.run(&headless).unwrap();
+
+    let mut failed = 0;
+
+    for result in &results {
+        match &result.status {
+            SnapshotStatus::Passed => {
+                println!("ok       {}", result.config.display());
+            },
+            SnapshotStatus::Missing => {
+                failed += 1;
+                println!("missing  {} (run with --bless to create it)", result.config.display());
+            },
+            SnapshotStatus::Failed(c) => {
+                failed += 1;
+                println!(
+                    "FAILED   {} ({} pixels differ, max delta {})",
+                    result.config.display(),
+                    c.mismatched,
+                    c.max_delta,
+                );
+            },
+        }
+    }
+
+    println!("{} snapshots, {} failed", results.len(), failed);
+
+    if failed > 0 {
+        process::exit(1);
+    }
+}
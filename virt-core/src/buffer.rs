@@ -1,5 +1,7 @@
 use vulkano::buffer::{CpuAccessibleBuffer, BufferUsage};
+use vulkano::descriptor::DescriptorSet;
 
+use std::fmt;
 use std::sync::Arc;
 
 use crate::vector::Vector;
@@ -8,14 +10,97 @@ use crate::error::{CoreError, Result};
 #[derive(Debug, Clone)]
 pub struct Buffer {
     pub vertex_buffer: Arc<CpuAccessibleBuffer<[Vector]>>,
-    pub index_buffer: Arc<CpuAccessibleBuffer<[u16]>>,
+    pub index_buffer: Arc<CpuAccessibleBuffer<[u32]>>,
 }
 
 impl Buffer {
-    pub fn new(vertex_buffer: Arc<CpuAccessibleBuffer<[Vector]>>, index_buffer: Arc<CpuAccessibleBuffer<[u16]>>) -> Buffer {
+    pub fn new(vertex_buffer: Arc<CpuAccessibleBuffer<[Vector]>>, index_buffer: Arc<CpuAccessibleBuffer<[u32]>>) -> Buffer {
         Buffer {
             vertex_buffer,
             index_buffer,
         }
     }
-}
\ No newline at end of file
+}
+
+/// TexturedVertex is a point in widget space together with its normalized texture coordinate.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct TexturedVertex {
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+}
+
+vulkano::impl_vertex!(TexturedVertex, position, uv);
+
+impl TexturedVertex {
+    pub fn new(position: [f32; 2], uv: [f32; 2]) -> TexturedVertex {
+        TexturedVertex {
+            position,
+            uv,
+        }
+    }
+}
+
+/// ColoredVertex is a point in widget space carrying its own premultiplied color,
+/// so shapes of different colors can share one draw call.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct ColoredVertex {
+    pub position: [f32; 2],
+    pub color: [f32; 4],
+}
+
+vulkano::impl_vertex!(ColoredVertex, position, color);
+
+impl ColoredVertex {
+    pub fn new(position: [f32; 2], color: [f32; 4]) -> ColoredVertex {
+        ColoredVertex {
+            position,
+            color,
+        }
+    }
+}
+
+/// BatchBuffer holds the uploaded geometry of a Batch.
+#[derive(Debug, Clone)]
+pub struct BatchBuffer {
+    pub vertex_buffer: Arc<CpuAccessibleBuffer<[ColoredVertex]>>,
+    pub index_buffer: Arc<CpuAccessibleBuffer<[u32]>>,
+}
+
+impl BatchBuffer {
+    pub fn new(vertex_buffer: Arc<CpuAccessibleBuffer<[ColoredVertex]>>, index_buffer: Arc<CpuAccessibleBuffer<[u32]>>) -> BatchBuffer {
+        BatchBuffer {
+            vertex_buffer,
+            index_buffer,
+        }
+    }
+}
+
+/// TexturedBuffer holds uploaded geometry together with the descriptor set binding its texture.
+/// Gradients reuse it with plain Vector vertices, the texture being their color ramp.
+#[derive(Clone)]
+pub struct TexturedBuffer<V = TexturedVertex> {
+    pub vertex_buffer: Arc<CpuAccessibleBuffer<[V]>>,
+    pub index_buffer: Arc<CpuAccessibleBuffer<[u32]>>,
+    pub set: Arc<dyn DescriptorSet + Send + Sync>,
+}
+
+impl<V> TexturedBuffer<V> {
+    pub fn new(
+        vertex_buffer: Arc<CpuAccessibleBuffer<[V]>>,
+        index_buffer: Arc<CpuAccessibleBuffer<[u32]>>,
+        set: Arc<dyn DescriptorSet + Send + Sync>,
+    ) -> TexturedBuffer<V> {
+        TexturedBuffer {
+            vertex_buffer,
+            index_buffer,
+            set,
+        }
+    }
+}
+
+impl<V> fmt::Debug for TexturedBuffer<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // The descriptor set has no Debug implementation
+        f.debug_struct("TexturedBuffer").finish()
+    }
+}
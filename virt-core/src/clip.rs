@@ -0,0 +1,238 @@
+use crate::render::{Renderer, BlendMode};
+use crate::buffer::{Buffer, BatchBuffer, ColoredVertex, TexturedBuffer, TexturedVertex};
+use crate::tessellate::{self, Mesh};
+use crate::gradient::{Gradient, GradientSpan};
+use crate::transform::Transform;
+use crate::software::SoftwareRenderer;
+use crate::vector::Vector;
+use crate::color::Color;
+use crate::frame::Frame;
+use crate::shape::Shape;
+use crate::error::Result;
+
+/// Clip restricts everything below a scene node to the inside of a shape, given in the
+/// space of the node. The region is every triangle the shape draws, so a stroked circle
+/// clips to a ring.
+#[derive(Debug)]
+pub struct Clip {
+    pub mesh: Mesh,
+
+    buffer: Option<Buffer>,
+}
+
+/// Every triangle a shape draws, taken through transform.
+pub fn capture(shape: &mut dyn Shape, resolution: Vector, transform: Transform) -> Result<Mesh> {
+    let mut capture = Capture {
+        resolution,
+        transform,
+        mesh: Mesh::new(),
+    };
+
+    shape.draw(&mut capture)?;
+
+    Ok(capture.mesh)
+}
+
+impl Clip {
+    pub fn new(shape: &mut dyn Shape, resolution: Vector) -> Result<Clip> {
+        Ok(Clip {
+            mesh: capture(shape, resolution, Transform::identity())?,
+            buffer: None,
+        })
+    }
+
+    // Same test the renderers apply, so what is hit is what is drawn
+    pub fn contains(&self, v: Vector) -> bool {
+        self.mesh.indices.chunks(3).any(|i| {
+            i.len() == 3 && tessellate::triangle_contains(
+                v,
+                self.mesh.vertices[i[0] as usize],
+                self.mesh.vertices[i[1] as usize],
+                self.mesh.vertices[i[2] as usize],
+            )
+        })
+    }
+
+    pub fn push(&mut self, renderer: &mut dyn Renderer) -> Result<()> {
+        renderer.push_clip(&mut self.buffer, &self.mesh.vertices, &self.mesh.indices)
+    }
+}
+
+/// MaskMode picks what of the mask image makes things visible.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum MaskMode {
+    Alpha,
+    Luminance,
+}
+
+/// Mask fades everything below a scene node by an image stretched over a rectangle in the
+/// space of the node. The masked content is rendered offscreen, multiplied with the mask
+/// and drawn back as one texture, so nothing outside the rectangle is left.
+#[derive(Debug)]
+pub struct Mask {
+    pub position: Vector,
+    pub size: Vector,
+
+    // One value per pixel of the mask image, from 0 to 1
+    values: Vec<f32>,
+    width: u32,
+    height: u32,
+}
+
+impl Mask {
+    pub fn new(image: &Frame, mode: MaskMode, position: Vector, size: Vector) -> Mask {
+        let values = image.data
+            .chunks(4)
+            .map(|px| {
+                let a = px[3] as f32 / u8::MAX as f32;
+
+                match mode {
+                    MaskMode::Alpha => a,
+                    MaskMode::Luminance => {
+                        (0.2126f32 * px[0] as f32 + 0.7152f32 * px[1] as f32 + 0.0722f32 * px[2] as f32) / u8::MAX as f32 * a
+                    },
+                }
+            })
+            .collect();
+
+        Mask {
+            position,
+            size,
+            values,
+            width: image.width,
+            height: image.height,
+        }
+    }
+
+    /// Multiplies content rendered over the rectangle of the mask with it.
+    pub fn apply(&self, offscreen: &mut SoftwareRenderer) {
+        let (width, height) = (offscreen.width, offscreen.height);
+
+        for y in 0..height {
+            for x in 0..width {
+                // Nearest mask pixel, the image is stretched over the whole rectangle
+                let mx = ((x as f32 + 0.5f32) / width as f32 * self.width as f32) as u32;
+                let my = ((y as f32 + 0.5f32) / height as f32 * self.height as f32) as u32;
+                let m = self.values[(my.min(self.height - 1) * self.width + mx.min(self.width - 1)) as usize];
+
+                for c in offscreen.pixels[(y * width + x) as usize].iter_mut() {
+                    *c *= m;
+                }
+            }
+        }
+    }
+}
+
+// Collects the triangles a shape draws, whatever it draws them with
+struct Capture {
+    resolution: Vector,
+    transform: Transform,
+    mesh: Mesh,
+}
+
+impl Capture {
+    fn add(&mut self, vertices: impl Iterator<Item = Vector>, indices: impl Iterator<Item = u32>) {
+        let first = self.mesh.vertices.len() as u32;
+        let transform = self.transform;
+
+        self.mesh.vertices.extend(vertices.map(|v| transform.apply(v)));
+        self.mesh.indices.extend(indices.map(|i| first + i));
+    }
+}
+
+impl Renderer for Capture {
+    fn resolution(&self) -> Vector {
+        self.resolution
+    }
+
+    fn begin(&mut self, _clear: Color) -> Result<()> {
+        Ok(())
+    }
+
+    fn transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    fn scale(&self) -> f32 {
+        self.transform.norm()
+    }
+
+    fn blend(&mut self, _mode: BlendMode) {}
+
+    fn fill(&mut self,
+        _buffer: &mut Option<Buffer>,
+        vertices: &[Vector],
+        indices: &[u32],
+        _color: Color,
+    ) -> Result<()> {
+        self.add(vertices.iter().cloned(), indices.iter().cloned());
+
+        Ok(())
+    }
+
+    fn rounded_rect(&mut self,
+        _buffer: &mut Option<Buffer>,
+        position: Vector,
+        size: Vector,
+        radius: [f32; 4],
+        _color: Color,
+    ) -> Result<()> {
+        let outline = tessellate::rounded_rect(position, size, radius);
+        let indices = tessellate::triangulate(&outline);
+
+        self.add(outline.into_iter(), indices.into_iter());
+
+        Ok(())
+    }
+
+    fn textured(&mut self,
+        _buffer: &mut Option<TexturedBuffer>,
+        vertices: &[TexturedVertex],
+        indices: &[u32],
+        _image: &Frame,
+        _repeat: bool,
+        _color: Color,
+    ) -> Result<()> {
+        self.add(vertices.iter().map(|v| Vector::new(v.position[0], v.position[1])), indices.iter().cloned());
+
+        Ok(())
+    }
+
+    fn gradient(&mut self,
+        _buffer: &mut Option<TexturedBuffer<Vector>>,
+        vertices: &[Vector],
+        indices: &[u32],
+        _gradient: &Gradient,
+        _span: GradientSpan,
+    ) -> Result<()> {
+        self.add(vertices.iter().cloned(), indices.iter().cloned());
+
+        Ok(())
+    }
+
+    fn batch(&mut self,
+        _buffer: &mut Option<BatchBuffer>,
+        vertices: &[ColoredVertex],
+        indices: &[u32],
+    ) -> Result<()> {
+        self.add(vertices.iter().map(|v| Vector::new(v.position[0], v.position[1])), indices.iter().copied());
+
+        Ok(())
+    }
+
+    fn push_clip(&mut self,
+        _buffer: &mut Option<Buffer>,
+        _vertices: &[Vector],
+        _indices: &[u32],
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn pop_clip(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
@@ -1,4 +1,8 @@
-#[derive(Debug, Copy, Clone)]
+use std::str::FromStr;
+
+use crate::error::{CoreError, Result};
+
+#[derive(Debug, PartialEq, Copy, Clone)]
 pub struct Color {
     r: u8,
     g: u8,
@@ -22,12 +26,88 @@ impl Color {
         }
     }
 
-    pub fn from_hex(c: Vec<u8>) -> Color {
-        Color {
-            r: c[0],
-            g: c[1],
-            b: c[2],
-            a: c[3],
+    /// Color from hue in degrees, saturation, lightness and alpha from 0 to 1.
+    pub fn from_hsla(h: f32, s: f32, l: f32, a: f32) -> Color {
+        let h = h.rem_euclid(360f32) / 60f32;
+        let (s, l) = (s.max(0f32).min(1f32), l.max(0f32).min(1f32));
+
+        let chroma = (1f32 - (2f32 * l - 1f32).abs()) * s;
+        let x = chroma * (1f32 - (h % 2f32 - 1f32).abs());
+        let m = l - chroma / 2f32;
+
+        let (r, g, b) = match h as u32 {
+            0 => (chroma, x, 0f32),
+            1 => (x, chroma, 0f32),
+            2 => (0f32, chroma, x),
+            3 => (0f32, x, chroma),
+            4 => (x, 0f32, chroma),
+            _ => (chroma, 0f32, x),
+        };
+
+        Color::from_unit([r + m, g + m, b + m, a])
+    }
+
+    /// Parses a color the way configs write them: #rgb, #rgba, #rrggbb, #rrggbbaa,
+    /// rgb(), rgba(), hsl(), hsla() or a CSS color name.
+    pub fn parse(value: &str) -> Result<Color> {
+        let invalid = |reason: &str| CoreError::InvalidColor(value.to_string(), reason.to_string());
+        let v = value.trim().to_ascii_lowercase();
+
+        if let Some(digits) = v.strip_prefix('#') {
+            return parse_hex(digits).ok_or_else(|| invalid("expected #rgb, #rgba, #rrggbb or #rrggbbaa"));
+        }
+
+        if let Some(open) = v.find('(') {
+            let name = v[..open].trim();
+
+            let args = match v[open + 1..].strip_suffix(')') {
+                Some(a) => a,
+                None => return Err(invalid("missing closing parenthesis")),
+            };
+
+            // Both the comma separated and the space separated syntax, with the alpha after a slash
+            let args: Vec<&str> = args
+                .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+                .filter(|a| !a.is_empty())
+                .collect();
+
+            if args.len() != 3 && args.len() != 4 {
+                return Err(invalid("expected 3 or 4 arguments"));
+            }
+
+            let alpha = match args.get(3) {
+                Some(a) => parse_unit(a).ok_or_else(|| invalid("alpha has to be a number from 0 to 1 or a percentage"))?,
+                None => 1f32,
+            };
+
+            return match name {
+                "rgb" | "rgba" => {
+                    let mut channels = [0u8; 3];
+
+                    for (c, a) in channels.iter_mut().zip(args.iter()) {
+                        *c = parse_channel(a).ok_or_else(|| invalid("red, green and blue have to be numbers from 0 to 255 or percentages"))?;
+                    }
+
+                    Ok(Color::new(channels[0], channels[1], channels[2], to_byte(alpha)))
+                },
+                "hsl" | "hsla" => {
+                    let h = parse_angle(args[0]).ok_or_else(|| invalid("hue has to be an angle in degrees"))?;
+                    let s = parse_percentage(args[1]).ok_or_else(|| invalid("saturation has to be a percentage"))?;
+                    let l = parse_percentage(args[2]).ok_or_else(|| invalid("lightness has to be a percentage"))?;
+
+                    Ok(Color::from_hsla(h, s, l, alpha))
+                },
+                _ => Err(invalid("unknown color function, expected rgb, rgba, hsl or hsla")),
+            };
+        }
+
+        if v == "transparent" {
+            return Ok(Color::default());
+        }
+
+        match NAMED.iter().find(|(n, _)| *n == v) {
+            Some((_, c)) => Ok(Color::new(c[0], c[1], c[2], u8::MAX)),
+            None => Err(invalid("not a hex value, color function or CSS color name")),
         }
     }
 
@@ -40,4 +120,603 @@ impl Color {
             a
         ]
     }
+
+    /// Premultiplied red, green and blue in linear light, and alpha, what renderers draw with.
+    pub fn to_premultiplied_linear(&self) -> [f32; 4] {
+        let [r, g, b, a] = self.to_linear();
+
+        [r * a, g * a, b * a, a]
+    }
+
+    pub fn rgba(&self) -> [u8; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    // Channels from 0 to 1, not premultiplied
+    fn unit(&self) -> [f32; 4] {
+        [
+            self.r as f32 / u8::MAX as f32,
+            self.g as f32 / u8::MAX as f32,
+            self.b as f32 / u8::MAX as f32,
+            self.a as f32 / u8::MAX as f32,
+        ]
+    }
+
+    /// Color from red, green, blue and alpha from 0 to 1, not premultiplied.
+    pub fn from_unit(c: [f32; 4]) -> Color {
+        Color::new(to_byte(c[0]), to_byte(c[1]), to_byte(c[2]), to_byte(c[3]))
+    }
+
+    /// Hue in degrees, saturation, lightness and alpha from 0 to 1.
+    pub fn to_hsla(&self) -> [f32; 4] {
+        let [r, g, b, a] = self.unit();
+        let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+        let chroma = max - min;
+
+        let l = (max + min) / 2f32;
+        let s = if chroma == 0f32 { 0f32 } else { chroma / (1f32 - (2f32 * l - 1f32).abs()) };
+
+        [hue(r, g, b, max, chroma), s, l, a]
+    }
+
+    /// Color from hue in degrees, saturation, value and alpha from 0 to 1.
+    pub fn from_hsva(h: f32, s: f32, v: f32, a: f32) -> Color {
+        let (s, v) = (s.max(0f32).min(1f32), v.max(0f32).min(1f32));
+
+        // The same color in HSL, both share the hue
+        let l = v * (1f32 - s / 2f32);
+        let sl = if l == 0f32 || l == 1f32 { 0f32 } else { (v - l) / l.min(1f32 - l) };
+
+        Color::from_hsla(h, sl, l, a)
+    }
+
+    /// Hue in degrees, saturation, value and alpha from 0 to 1.
+    pub fn to_hsva(&self) -> [f32; 4] {
+        let [r, g, b, a] = self.unit();
+        let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+        let chroma = max - min;
+
+        let s = if max == 0f32 { 0f32 } else { chroma / max };
+
+        [hue(r, g, b, max, chroma), s, max, a]
+    }
+
+    /// Color from OKLab lightness, a and b, and alpha from 0 to 1. Colors outside sRGB are clamped.
+    pub fn from_oklab(l: f32, a: f32, b: f32, alpha: f32) -> Color {
+        let l_ = (l + 0.3963377774f32 * a + 0.2158037573f32 * b).powi(3);
+        let m_ = (l - 0.1055613458f32 * a - 0.0638541728f32 * b).powi(3);
+        let s_ = (l - 0.0894841775f32 * a - 1.2914855480f32 * b).powi(3);
+
+        Color::from_linear([
+            4.0767416621f32 * l_ - 3.3077115913f32 * m_ + 0.2309699292f32 * s_,
+            -1.2684380046f32 * l_ + 2.6097574011f32 * m_ - 0.3413193965f32 * s_,
+            -0.0041960863f32 * l_ - 0.7034186147f32 * m_ + 1.7076147010f32 * s_,
+            alpha,
+        ])
+    }
+
+    /// OKLab lightness, a and b, and alpha, a perceptual space where equal distances look
+    /// about equally different.
+    pub fn to_oklab(&self) -> [f32; 4] {
+        let [r, g, b, alpha] = self.to_linear();
+
+        let l = (0.4122214708f32 * r + 0.5363325363f32 * g + 0.0514459929f32 * b).cbrt();
+        let m = (0.2119034982f32 * r + 0.6806995451f32 * g + 0.1073969566f32 * b).cbrt();
+        let s = (0.0883024619f32 * r + 0.2817188376f32 * g + 0.6299787005f32 * b).cbrt();
+
+        [
+            0.2104542553f32 * l + 0.7936177850f32 * m - 0.0040720468f32 * s,
+            1.9779984951f32 * l - 2.4285922050f32 * m + 0.4505937099f32 * s,
+            0.0259040371f32 * l + 0.7827717662f32 * m - 0.8086757660f32 * s,
+            alpha,
+        ]
+    }
+
+    /// Color from linear light red, green, blue and alpha from 0 to 1.
+    pub fn from_linear(c: [f32; 4]) -> Color {
+        Color::from_unit([linear_to_srgb(c[0]), linear_to_srgb(c[1]), linear_to_srgb(c[2]), c[3]])
+    }
+
+    /// Red, green and blue in linear light, what blending and lighting math expects, and alpha.
+    pub fn to_linear(&self) -> [f32; 4] {
+        let [r, g, b, a] = self.unit();
+
+        [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a]
+    }
+
+    /// Interpolates from self at t = 0 to other at t = 1 in OKLab, so the colors in between
+    /// change evenly in lightness and do not turn gray or dark on the way.
+    pub fn mix(&self, other: Color, t: f32) -> Color {
+        let t = t.max(0f32).min(1f32);
+        let (from, to) = (self.to_oklab(), other.to_oklab());
+
+        // A fully transparent end has no color of its own, only its alpha changes
+        let (from, to) = match (self.a, other.a) {
+            (0, _) => ([to[0], to[1], to[2], from[3]], to),
+            (_, 0) => (from, [from[0], from[1], from[2], to[3]]),
+            _ => (from, to),
+        };
+
+        let mut out = [0f32; 4];
+        for ((o, f), e) in out.iter_mut().zip(from.iter()).zip(to.iter()) {
+            *o = f + (e - f) * t;
+        }
+
+        Color::from_oklab(out[0], out[1], out[2], out[3])
+    }
+
+    /// Raises the HSL lightness by amount, from 0 to 1.
+    pub fn lighten(&self, amount: f32) -> Color {
+        let [h, s, l, a] = self.to_hsla();
+
+        Color::from_hsla(h, s, l + amount, a)
+    }
+
+    /// Lowers the HSL lightness by amount, from 0 to 1.
+    pub fn darken(&self, amount: f32) -> Color {
+        self.lighten(-amount)
+    }
+
+    /// Raises the HSL saturation by amount, from 0 to 1, negative amounts desaturate.
+    pub fn saturate(&self, amount: f32) -> Color {
+        let [h, s, l, a] = self.to_hsla();
+
+        Color::from_hsla(h, s + amount, l, a)
+    }
+
+    /// Lowers the HSL saturation by amount, from 0 to 1.
+    pub fn desaturate(&self, amount: f32) -> Color {
+        self.saturate(-amount)
+    }
+
+    /// Same color with alpha from 0 to 1.
+    pub fn with_alpha(&self, alpha: f32) -> Color {
+        Color::new(self.r, self.g, self.b, to_byte(alpha))
+    }
+
+    /// Relative luminance as defined by WCAG, 0 for black and 1 for white. Alpha is ignored.
+    pub fn luminance(&self) -> f32 {
+        let [r, g, b, _] = self.to_linear();
+
+        0.2126f32 * r + 0.7152f32 * g + 0.0722f32 * b
+    }
+
+    /// WCAG contrast ratio between two colors, from 1 for the same luminance to 21 for
+    /// black on white. Text wants at least 4.5.
+    pub fn contrast(&self, other: Color) -> f32 {
+        let (a, b) = (self.luminance(), other.luminance());
+
+        (a.max(b) + 0.05f32) / (a.min(b) + 0.05f32)
+    }
+}
+
+/// Decodes an sRGB encoded channel from 0 to 1 into linear light.
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045f32 {
+        c / 12.92f32
+    } else {
+        ((c + 0.055f32) / 1.055f32).powf(2.4f32)
+    }
+}
+
+/// Encodes a linear light channel from 0 to 1 as sRGB.
+pub fn linear_to_srgb(c: f32) -> f32 {
+    let c = c.max(0f32).min(1f32);
+
+    if c <= 0.0031308f32 {
+        c * 12.92f32
+    } else {
+        1.055f32 * c.powf(1f32 / 2.4f32) - 0.055f32
+    }
+}
+
+/// Turns a premultiplied sRGB pixel from 0 to 1, as textures hold them, into premultiplied
+/// linear light, the same as the textured shaders do after sampling.
+pub fn decode_pixel(px: [f32; 4]) -> [f32; 4] {
+    let a = px[3];
+
+    if a <= 0f32 {
+        return [0f32; 4];
+    }
+
+    [srgb_to_linear(px[0] / a) * a, srgb_to_linear(px[1] / a) * a, srgb_to_linear(px[2] / a) * a, a]
+}
+
+/// Turns a premultiplied linear pixel into the premultiplied sRGB bytes textures hold,
+/// the inverse of decode_pixel.
+pub fn encode_pixel(px: [f32; 4]) -> [u8; 4] {
+    let a = px[3].max(0f32).min(1f32);
+
+    if a <= 0f32 {
+        return [0u8; 4];
+    }
+
+    let encode = |c: f32| to_byte(linear_to_srgb(c / a) * a);
+
+    [encode(px[0]), encode(px[1]), encode(px[2]), to_byte(a)]
+}
+
+// Hue in degrees shared by HSL and HSV, 0 for grays
+fn hue(r: f32, g: f32, b: f32, max: f32, chroma: f32) -> f32 {
+    if chroma == 0f32 {
+        return 0f32;
+    }
+
+    let h = if max == r {
+        ((g - b) / chroma).rem_euclid(6f32)
+    } else if max == g {
+        (b - r) / chroma + 2f32
+    } else {
+        (r - g) / chroma + 4f32
+    };
+
+    h * 60f32
+}
+
+impl FromStr for Color {
+    type Err = CoreError;
+
+    fn from_str(s: &str) -> Result<Color> {
+        Color::parse(s)
+    }
+}
+
+fn to_byte(v: f32) -> u8 {
+    (v.max(0f32).min(1f32) * u8::MAX as f32).round() as u8
+}
+
+// Short forms repeat every digit, #f80 is #ff8800
+fn parse_hex(digits: &str) -> Option<Color> {
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let values: Vec<u8> = match digits.len() {
+        3 | 4 => digits
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| (d * 17) as u8))
+            .collect::<Option<_>>()?,
+        6 | 8 => (0..digits.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok())
+            .collect::<Option<_>>()?,
+        _ => return None,
+    };
+
+    Some(Color::new(values[0], values[1], values[2], values.get(3).cloned().unwrap_or(u8::MAX)))
+}
+
+// 0 to 255, or a percentage of it
+fn parse_channel(arg: &str) -> Option<u8> {
+    match arg.strip_suffix('%') {
+        Some(p) => p.parse::<f32>().ok().map(|p| to_byte(p / 100f32)),
+        None => arg.parse::<f32>().ok().filter(|v| v.is_finite()).map(|v| v.max(0f32).min(u8::MAX as f32).round() as u8),
+    }
+}
+
+// 0 to 1, or a percentage
+fn parse_unit(arg: &str) -> Option<f32> {
+    match arg.strip_suffix('%') {
+        Some(p) => p.parse::<f32>().ok().map(|p| p / 100f32),
+        None => arg.parse::<f32>().ok(),
+    }
+    .filter(|v| v.is_finite())
+}
+
+// Only a percentage, like CSS
+fn parse_percentage(arg: &str) -> Option<f32> {
+    arg.strip_suffix('%')?.parse::<f32>().ok().filter(|v| v.is_finite()).map(|p| p / 100f32)
+}
+
+// Degrees, with or without the deg unit
+fn parse_angle(arg: &str) -> Option<f32> {
+    arg.strip_suffix("deg").unwrap_or(arg).parse::<f32>().ok().filter(|v| v.is_finite())
+}
+
+// The CSS named colors
+const NAMED: [(&str, [u8; 3]); 148] = [
+    ("aliceblue", [240, 248, 255]),
+    ("antiquewhite", [250, 235, 215]),
+    ("aqua", [0, 255, 255]),
+    ("aquamarine", [127, 255, 212]),
+    ("azure", [240, 255, 255]),
+    ("beige", [245, 245, 220]),
+    ("bisque", [255, 228, 196]),
+    ("black", [0, 0, 0]),
+    ("blanchedalmond", [255, 235, 205]),
+    ("blue", [0, 0, 255]),
+    ("blueviolet", [138, 43, 226]),
+    ("brown", [165, 42, 42]),
+    ("burlywood", [222, 184, 135]),
+    ("cadetblue", [95, 158, 160]),
+    ("chartreuse", [127, 255, 0]),
+    ("chocolate", [210, 105, 30]),
+    ("coral", [255, 127, 80]),
+    ("cornflowerblue", [100, 149, 237]),
+    ("cornsilk", [255, 248, 220]),
+    ("crimson", [220, 20, 60]),
+    ("cyan", [0, 255, 255]),
+    ("darkblue", [0, 0, 139]),
+    ("darkcyan", [0, 139, 139]),
+    ("darkgoldenrod", [184, 134, 11]),
+    ("darkgray", [169, 169, 169]),
+    ("darkgreen", [0, 100, 0]),
+    ("darkgrey", [169, 169, 169]),
+    ("darkkhaki", [189, 183, 107]),
+    ("darkmagenta", [139, 0, 139]),
+    ("darkolivegreen", [85, 107, 47]),
+    ("darkorange", [255, 140, 0]),
+    ("darkorchid", [153, 50, 204]),
+    ("darkred", [139, 0, 0]),
+    ("darksalmon", [233, 150, 122]),
+    ("darkseagreen", [143, 188, 143]),
+    ("darkslateblue", [72, 61, 139]),
+    ("darkslategray", [47, 79, 79]),
+    ("darkslategrey", [47, 79, 79]),
+    ("darkturquoise", [0, 206, 209]),
+    ("darkviolet", [148, 0, 211]),
+    ("deeppink", [255, 20, 147]),
+    ("deepskyblue", [0, 191, 255]),
+    ("dimgray", [105, 105, 105]),
+    ("dimgrey", [105, 105, 105]),
+    ("dodgerblue", [30, 144, 255]),
+    ("firebrick", [178, 34, 34]),
+    ("floralwhite", [255, 250, 240]),
+    ("forestgreen", [34, 139, 34]),
+    ("fuchsia", [255, 0, 255]),
+    ("gainsboro", [220, 220, 220]),
+    ("ghostwhite", [248, 248, 255]),
+    ("gold", [255, 215, 0]),
+    ("goldenrod", [218, 165, 32]),
+    ("gray", [128, 128, 128]),
+    ("green", [0, 128, 0]),
+    ("greenyellow", [173, 255, 47]),
+    ("grey", [128, 128, 128]),
+    ("honeydew", [240, 255, 240]),
+    ("hotpink", [255, 105, 180]),
+    ("indianred", [205, 92, 92]),
+    ("indigo", [75, 0, 130]),
+    ("ivory", [255, 255, 240]),
+    ("khaki", [240, 230, 140]),
+    ("lavender", [230, 230, 250]),
+    ("lavenderblush", [255, 240, 245]),
+    ("lawngreen", [124, 252, 0]),
+    ("lemonchiffon", [255, 250, 205]),
+    ("lightblue", [173, 216, 230]),
+    ("lightcoral", [240, 128, 128]),
+    ("lightcyan", [224, 255, 255]),
+    ("lightgoldenrodyellow", [250, 250, 210]),
+    ("lightgray", [211, 211, 211]),
+    ("lightgreen", [144, 238, 144]),
+    ("lightgrey", [211, 211, 211]),
+    ("lightpink", [255, 182, 193]),
+    ("lightsalmon", [255, 160, 122]),
+    ("lightseagreen", [32, 178, 170]),
+    ("lightskyblue", [135, 206, 250]),
+    ("lightslategray", [119, 136, 153]),
+    ("lightslategrey", [119, 136, 153]),
+    ("lightsteelblue", [176, 196, 222]),
+    ("lightyellow", [255, 255, 224]),
+    ("lime", [0, 255, 0]),
+    ("limegreen", [50, 205, 50]),
+    ("linen", [250, 240, 230]),
+    ("magenta", [255, 0, 255]),
+    ("maroon", [128, 0, 0]),
+    ("mediumaquamarine", [102, 205, 170]),
+    ("mediumblue", [0, 0, 205]),
+    ("mediumorchid", [186, 85, 211]),
+    ("mediumpurple", [147, 112, 219]),
+    ("mediumseagreen", [60, 179, 113]),
+    ("mediumslateblue", [123, 104, 238]),
+    ("mediumspringgreen", [0, 250, 154]),
+    ("mediumturquoise", [72, 209, 204]),
+    ("mediumvioletred", [199, 21, 133]),
+    ("midnightblue", [25, 25, 112]),
+    ("mintcream", [245, 255, 250]),
+    ("mistyrose", [255, 228, 225]),
+    ("moccasin", [255, 228, 181]),
+    ("navajowhite", [255, 222, 173]),
+    ("navy", [0, 0, 128]),
+    ("oldlace", [253, 245, 230]),
+    ("olive", [128, 128, 0]),
+    ("olivedrab", [107, 142, 35]),
+    ("orange", [255, 165, 0]),
+    ("orangered", [255, 69, 0]),
+    ("orchid", [218, 112, 214]),
+    ("palegoldenrod", [238, 232, 170]),
+    ("palegreen", [152, 251, 152]),
+    ("paleturquoise", [175, 238, 238]),
+    ("palevioletred", [219, 112, 147]),
+    ("papayawhip", [255, 239, 213]),
+    ("peachpuff", [255, 218, 185]),
+    ("peru", [205, 133, 63]),
+    ("pink", [255, 192, 203]),
+    ("plum", [221, 160, 221]),
+    ("powderblue", [176, 224, 230]),
+    ("purple", [128, 0, 128]),
+    ("rebeccapurple", [102, 51, 153]),
+    ("red", [255, 0, 0]),
+    ("rosybrown", [188, 143, 143]),
+    ("royalblue", [65, 105, 225]),
+    ("saddlebrown", [139, 69, 19]),
+    ("salmon", [250, 128, 114]),
+    ("sandybrown", [244, 164, 96]),
+    ("seagreen", [46, 139, 87]),
+    ("seashell", [255, 245, 238]),
+    ("sienna", [160, 82, 45]),
+    ("silver", [192, 192, 192]),
+    ("skyblue", [135, 206, 235]),
+    ("slateblue", [106, 90, 205]),
+    ("slategray", [112, 128, 144]),
+    ("slategrey", [112, 128, 144]),
+    ("snow", [255, 250, 250]),
+    ("springgreen", [0, 255, 127]),
+    ("steelblue", [70, 130, 180]),
+    ("tan", [210, 180, 140]),
+    ("teal", [0, 128, 128]),
+    ("thistle", [216, 191, 216]),
+    ("tomato", [255, 99, 71]),
+    ("turquoise", [64, 224, 208]),
+    ("violet", [238, 130, 238]),
+    ("wheat", [245, 222, 179]),
+    ("white", [255, 255, 255]),
+    ("whitesmoke", [245, 245, 245]),
+    ("yellow", [255, 255, 0]),
+    ("yellowgreen", [154, 205, 50]),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(value: &str) -> [u8; 4] {
+        Color::parse(value).unwrap().rgba()
+    }
+
+    #[test]
+    fn parses_hex() {
+        assert_eq!(parsed("#f80"), [255, 136, 0, 255]);
+        assert_eq!(parsed("#f808"), [255, 136, 0, 136]);
+        assert_eq!(parsed("#ff8800"), [255, 136, 0, 255]);
+        assert_eq!(parsed("#FF880080"), [255, 136, 0, 128]);
+        assert_eq!(parsed("  #808080ff "), [128, 128, 128, 255]);
+    }
+
+    #[test]
+    fn parses_rgb() {
+        assert_eq!(parsed("rgb(255, 0, 128)"), [255, 0, 128, 255]);
+        assert_eq!(parsed("rgba(255, 0, 128, 0.5)"), [255, 0, 128, 128]);
+        assert_eq!(parsed("rgb(100% 0% 50% / 25%)"), [255, 0, 128, 64]);
+        assert_eq!(parsed("RGB(300, -5, 0)"), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn parses_hsl() {
+        assert_eq!(parsed("hsl(0, 100%, 50%)"), [255, 0, 0, 255]);
+        assert_eq!(parsed("hsl(120deg 100% 25%)"), [0, 128, 0, 255]);
+        assert_eq!(parsed("hsla(240, 100%, 50%, 0.5)"), [0, 0, 255, 128]);
+        assert_eq!(parsed("hsl(-120, 100%, 50%)"), [0, 0, 255, 255]);
+        assert_eq!(parsed("hsl(0, 0%, 100%)"), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn parses_names() {
+        assert_eq!(parsed("rebeccapurple"), [102, 51, 153, 255]);
+        assert_eq!(parsed("White"), [255, 255, 255, 255]);
+        assert_eq!(parsed("transparent"), [0, 0, 0, 0]);
+        assert_eq!("navy".parse::<Color>().unwrap().rgba(), [0, 0, 128, 255]);
+    }
+
+    #[test]
+    fn rejects_invalid() {
+        for value in [
+            "", "#", "#12", "#12345", "#1234567", "#ggg",
+            "rgb(1, 2)", "rgb(1, 2, 3, 4, 5)", "rgb(a, 2, 3)", "rgb(1, 2, 3",
+            "hsl(0, 50, 50%)", "hsl(x, 50%, 50%)", "rgba(0, 0, 0, nan)",
+            "cmyk(0, 0, 0, 0)", "notacolor",
+        ].iter() {
+            match Color::parse(value) {
+                Err(CoreError::InvalidColor(v, _)) => assert_eq!(&v, value),
+                other => panic!("{:?} parsed as {:?}", value, other),
+            }
+        }
+    }
+
+    fn close(a: [u8; 4], b: [u8; 4]) -> bool {
+        a.iter().zip(b.iter()).all(|(&x, &y)| (x as i32 - y as i32).abs() <= 1)
+    }
+
+    // Every channel from 0 to 255 in steps of 17, and a few in between
+    fn samples() -> Vec<Color> {
+        let steps: Vec<u8> = (0..=15).map(|i| i * 17).chain(vec![1, 128, 200, 254]).collect();
+        let mut colors = Vec::new();
+
+        for &r in steps.iter() {
+            for &g in steps.iter() {
+                for &b in steps.iter() {
+                    colors.push(Color::new(r, g, b, 200));
+                }
+            }
+        }
+
+        colors
+    }
+
+    #[test]
+    fn round_trips() {
+        for c in samples() {
+            let [h, s, l, a] = c.to_hsla();
+            assert!(close(Color::from_hsla(h, s, l, a).rgba(), c.rgba()), "hsl {:?}", c);
+
+            let [h, s, v, a] = c.to_hsva();
+            assert!(close(Color::from_hsva(h, s, v, a).rgba(), c.rgba()), "hsv {:?}", c);
+
+            let [l, a, b, alpha] = c.to_oklab();
+            assert!(close(Color::from_oklab(l, a, b, alpha).rgba(), c.rgba()), "oklab {:?}", c);
+
+            assert!(close(Color::from_linear(c.to_linear()).rgba(), c.rgba()), "linear {:?}", c);
+        }
+    }
+
+    #[test]
+    fn converts_to_oklab() {
+        let known = [
+            (Color::new(255, 255, 255, 255), [1f32, 0f32, 0f32]),
+            (Color::new(0, 0, 0, 255), [0f32, 0f32, 0f32]),
+            (Color::new(255, 0, 0, 255), [0.627955f32, 0.224863f32, 0.125846f32]),
+            (Color::new(0, 255, 0, 255), [0.866440f32, -0.233888f32, 0.179498f32]),
+            (Color::new(0, 0, 255, 255), [0.452014f32, -0.032457f32, -0.311528f32]),
+        ];
+
+        for (c, lab) in known.iter() {
+            let out = c.to_oklab();
+
+            for (o, l) in out.iter().zip(lab.iter()) {
+                assert!((o - l).abs() < 1e-3, "{:?} is {:?}", c, out);
+            }
+        }
+    }
+
+    #[test]
+    fn converts_to_hsl_and_hsv() {
+        assert_eq!(Color::new(255, 0, 0, 255).to_hsla(), [0f32, 1f32, 0.5f32, 1f32]);
+        assert_eq!(Color::new(0, 0, 255, 255).to_hsva(), [240f32, 1f32, 1f32, 1f32]);
+        assert_eq!(Color::from_hsva(120f32, 1f32, 0.5f32, 1f32).rgba(), [0, 128, 0, 255]);
+    }
+
+    #[test]
+    fn mixes() {
+        let (red, blue) = (Color::new(255, 0, 0, 255), Color::new(0, 0, 255, 128));
+
+        assert!(close(red.mix(blue, 0f32).rgba(), red.rgba()));
+        assert!(close(red.mix(blue, 1f32).rgba(), blue.rgba()));
+        assert_eq!(red.mix(blue, 0.5f32).rgba()[3], 192);
+
+        // A transparent end only fades the other one
+        let faded = Color::default().mix(blue, 0.5f32).rgba();
+        assert!(close([faded[0], faded[1], faded[2], 0], [0, 0, 255, 0]));
+        assert_eq!(faded[3], 64);
+    }
+
+    #[test]
+    fn adjusts_lightness_and_saturation() {
+        let red = Color::new(255, 0, 0, 255);
+
+        assert_eq!(red.lighten(0.2f32).rgba(), [255, 102, 102, 255]);
+        assert_eq!(red.darken(0.2f32).rgba(), [153, 0, 0, 255]);
+        assert_eq!(red.desaturate(1f32).rgba(), [128, 128, 128, 255]);
+        assert_eq!(Color::new(191, 64, 64, 255).saturate(0.5f32).rgba(), [255, 0, 0, 255]);
+        assert_eq!(red.lighten(2f32).rgba(), [255, 255, 255, 255]);
+        assert_eq!(red.with_alpha(0.5f32).rgba(), [255, 0, 0, 128]);
+    }
+
+    #[test]
+    fn measures_contrast() {
+        let (black, white) = (Color::new(0, 0, 0, 255), Color::new(255, 255, 255, 255));
+
+        assert!((black.contrast(white) - 21f32).abs() < 1e-4);
+        assert!((white.contrast(black) - 21f32).abs() < 1e-4);
+        assert_eq!(white.contrast(white), 1f32);
+        assert!((Color::new(118, 118, 118, 255).contrast(white) - 4.54f32).abs() < 0.01f32);
+    }
 }
@@ -1,19 +1,20 @@
 use vulkano::buffer::{CpuBufferPool};
 use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
 use vulkano::device::{Device, DeviceExtensions, Queue};
+use vulkano::format::Format;
 use vulkano::framebuffer::{FramebufferAbstract, RenderPassAbstract};
 use vulkano::image::ImageUsage;
 use vulkano::instance::{Instance, PhysicalDevice, PhysicalDeviceType};
 //use vulkano::pipeline::GraphicsPipelineAbstract;
 use vulkano::swapchain;
 use vulkano::swapchain::{
-    AcquireError, ColorSpace, FullscreenExclusive, PresentMode, SurfaceTransform, Swapchain, Surface
+    AcquireError, FullscreenExclusive, PresentMode, SurfaceTransform, Swapchain, Surface
 };
 use vulkano::{sync, sync::FlushError, sync::GpuFuture};
 
 use vulkano_win::VkSurfaceBuild;
 
-use winit::event_loop::EventLoop;
+use winit::event_loop::{ControlFlow, EventLoop};
 use winit::window::{Window, WindowBuilder, WindowId};
 use winit::dpi::{LogicalSize, LogicalPosition};
 
@@ -21,6 +22,7 @@ use cgmath::Vector2;
 
 use std::sync::Arc;
 use std::collections::HashMap;
+use std::time::Instant;
 
 use crate::util::*;
 //use crate::geometry::Vector;
@@ -30,25 +32,35 @@ use crate::widget::Widget;
 use crate::decoder::WidgetConfig;
 use crate::error::{CoreError, Result};
 use crate::pipelines::ShapesPipeline;
+use crate::render::{Backend, VulkanRenderer, VulkanUploader};
+use crate::software::SoftwareRenderer;
+use crate::present::present;
 
 
 pub struct CoreState {
+    // The Vulkan device, None when the widgets are drawn in software
+    pub gpu: Option<Gpu>,
+
+    pub surfaces: HashMap<WindowId, CoreSurface>,
+}
+
+/// Gpu is the Vulkan device every window is drawn with by the Vulkan backend.
+pub struct Gpu {
     pub instance: Arc<Instance>,
     pub physical_index: usize,
 
     pub queue: Arc<Queue>,
     pub device: Arc<Device>,
 
-    pub surfaces: HashMap<WindowId, CoreSurface>,
+    pub samples: u32,
 }
 
-impl CoreState {
-    pub fn new() -> Result<(CoreState, EventLoop<()>)>{
+impl Gpu {
+    pub fn new(cfg: &CoreConfig) -> Result<Gpu> {
         let instance = Instance::new(None, &vulkano_win::required_extensions(), None)?;
-        let physical_index = find_device_index(instance.clone(), PhysicalDeviceType::DiscreteGpu)?;
-        let physical = PhysicalDevice::from_index(&instance, physical_index).unwrap();
 
-        let event_loop = EventLoop::new();
+        let physical_index = find_device_index(instance.clone(), cfg.device.as_ref())?;
+        let physical = PhysicalDevice::from_index(&instance, physical_index).unwrap();
 
         let queue_family = physical
         .queue_families()
@@ -69,10 +81,39 @@ impl CoreState {
 
         let queue = queues.next().unwrap();
 
-        let mut surfaces = HashMap::new();
+        let samples = sample_count(&physical, cfg.msaa.unwrap_or(1));
 
+        Ok(Gpu {
+            instance,
+            physical_index,
+            queue,
+            device,
+            samples,
+        })
+    }
+}
+
+impl CoreState {
+    pub fn new() -> Result<(CoreState, EventLoop<()>)>{
         let cfg = CoreConfig::new()?;
 
+        // Without Vulkan, or without a device it can draw with, the windows are drawn in software
+        let gpu = match cfg.backend.unwrap_or(Backend::Vulkan) {
+            Backend::Vulkan => match Gpu::new(&cfg) {
+                Ok(gpu) => Some(gpu),
+                Err(CoreError::InstanceCreationError(_)) | Err(CoreError::NoSupportedPhysicalDevice) => {
+                    eprintln!("No Vulkan device found, drawing in software");
+                    None
+                },
+                Err(e) => return Err(e),
+            },
+            Backend::Software => None,
+        };
+
+        let event_loop = EventLoop::new();
+
+        let mut surfaces = HashMap::new();
+
         let widget_paths = widget_paths(cfg)?;
 
         for path in widget_paths {
@@ -81,141 +122,161 @@ impl CoreState {
                     //println!("{:?}", p.display());
                     let widget_config = decoder::decode(p.to_str().unwrap())?;
 
-                    let surface = CoreSurface::new(&physical, device.clone(), queue.clone(), &event_loop, instance.clone(), widget_config)?;
+                    let surface = CoreSurface::new(gpu.as_ref(), &event_loop, widget_config)?;
 
-                    surfaces.insert(surface.surface.window().id(), surface);
+                    surfaces.insert(surface.window().id(), surface);
                 },
                 Err(e) => return Err(CoreError::from(e)),
             }
         }
 
-        vulkano::impl_vertex!(Vector, position);
-
         //let buffer_pool: CpuBufferPool<Vector> = CpuBufferPool::vertex_buffer(device.clone());
 
         Ok((CoreState {
-            instance,
-            physical_index,
-            queue,
-            device,
+            gpu,
             surfaces,
         },
         event_loop))
     }
 
-    pub fn draw(&mut self, surface_id: WindowId) -> Result<()> {
-        let mut surface = self.surfaces.get_mut(&surface_id).unwrap();
+    /// Requests a redraw of every surface whose widget changed, or whose animation frame is due.
+    pub fn request_redraws(&mut self) {
+        let now = Instant::now();
 
-        surface.previous_frame_end.as_mut().unwrap().cleanup_finished();
+        for surface in self.surfaces.values_mut() {
+            surface.widget.tick(now);
 
-        if surface.recreate_swapchain {
-            let dimensions: [u32; 2] = surface.surface.window().inner_size().into();
-            let (new_swapchain, new_images) = surface.swapchain.recreate_with_dimensions(dimensions)?;
+            let recreate_swapchain = match &surface.target {
+                SurfaceTarget::Vulkan(v) => v.recreate_swapchain,
+                SurfaceTarget::Software(_) => false,
+            };
 
-            surface.swapchain = new_swapchain;
-            surface.framebuffers = window_size_dependent_setup(
-                &new_images,
-                surface.render_pass.clone(),
-                &mut surface.dynamic_state,
-            );
-            surface.recreate_swapchain = false;
+            if surface.widget.dirty || recreate_swapchain {
+                surface.window().request_redraw();
+            }
         }
+    }
 
-        let (image_num, suboptimal, acquire_future) =
-            match swapchain::acquire_next_image(surface.swapchain.clone(), None) {
-                Ok(r) => r,
-                Err(AcquireError::OutOfDate) => {
-                    surface.recreate_swapchain = true;
-                    return Ok(());
-                }
-                Err(e) => return Err(CoreError::from(e)),
-            };
+    /// Sleeps until the next event, or until the earliest frame an animation asked for.
+    pub fn control_flow(&self) -> ControlFlow {
+        let next = self.surfaces
+            .values()
+            .filter_map(|s| s.widget.redraw_at)
+            .min();
 
-        if suboptimal {
-            surface.recreate_swapchain = true;
+        match next {
+            Some(t) => ControlFlow::WaitUntil(t),
+            None => ControlFlow::Wait,
         }
-    
-        let mut builder = AutoCommandBufferBuilder::primary_one_time_submit(
-            self.device.clone(),
-            self.queue.family(),
-        )?;
+    }
 
-        surface.widget.draw(
-            self.device.clone(),
-            &mut builder, 
-            surface.framebuffers[image_num].clone(), 
-            &surface.pipelines,
-            &surface.dynamic_state,
-        )?;
-    
-        builder
-            .end_render_pass()
-            .unwrap();
-    
-        let command_buffer = builder.build()?;
-    
-        let future = surface.previous_frame_end
-            .take()
-            .unwrap()
-            .join(acquire_future)
-            .then_execute(self.queue.clone(), command_buffer)?
-            .then_swapchain_present(self.queue.clone(), surface.swapchain.clone(), image_num)
-            .then_signal_fence_and_flush();
-    
-        match future {
-            Ok(future) => {
-                surface.previous_frame_end = Some(future.boxed());
-            }
-            Err(FlushError::OutOfDate) => {
-                surface.recreate_swapchain = true;
-                surface.previous_frame_end = Some(sync::now(self.device.clone()).boxed());
-            }
-            Err(e) => {
-                surface.previous_frame_end = Some(sync::now(self.device.clone()).boxed());
-    
-                return Err(CoreError::from(e))
-            }
-        }
+    pub fn draw(&mut self, surface_id: WindowId) -> Result<()> {
+        let surface = self.surfaces.get_mut(&surface_id).unwrap();
 
-        Ok(())
+        match (&mut surface.target, &self.gpu) {
+            (SurfaceTarget::Vulkan(v), Some(gpu)) => v.draw(gpu, &mut surface.widget),
+            (SurfaceTarget::Software(window), _) => {
+                let mut renderer = SoftwareRenderer::new(surface.widget.bound.x() as u32, surface.widget.bound.y() as u32);
+
+                surface.widget.draw(&mut renderer)?;
+                present(window, &renderer.to_premultiplied_frame())?;
+
+                surface.widget.dirty = false;
+
+                Ok(())
+            },
+            (SurfaceTarget::Vulkan(_), None) => Ok(()),
+        }
     }
 }
 
+/// CoreSurface is the window of one widget, together with what it is drawn into.
 pub struct CoreSurface {
-    pub surface: Arc<Surface<Window>>,
-    pub swapchain: Arc<Swapchain<Window>>,
-    pub render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
-    pub dynamic_state: DynamicState,
-    pub recreate_swapchain: bool,
-    pub previous_frame_end: Option<Box<(dyn GpuFuture)>>,
-    pub framebuffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
-    pub pipelines: ShapesPipeline,
-    
+    pub target: SurfaceTarget,
+
     pub widget: Widget,
 
     pub cur_mouse_pos: Option<Vector>,
     pub las_mouse_pos: Option<Vector>,
 }
 
+pub enum SurfaceTarget {
+    Vulkan(VulkanSurface),
+    // Drawn by SoftwareRenderer and copied into the window
+    Software(Window),
+}
+
 impl CoreSurface {
-    fn new(physical: &PhysicalDevice, device: Arc<Device>, queue: Arc<Queue>, event_loop: &EventLoop<()>, instance: Arc<Instance>, config: WidgetConfig) -> Result<CoreSurface> {
+    fn new(gpu: Option<&Gpu>, event_loop: &EventLoop<()>, config: WidgetConfig) -> Result<CoreSurface> {
         let widget = Widget::new(config)?;
 
-        let surface = WindowBuilder::new()
+        let builder = WindowBuilder::new()
         .with_inner_size(LogicalSize::new(widget.bound.x(), widget.bound.y()))
         .with_decorations(false)    
         .with_transparent(true)
-        .with_resizable(false)
-        .build_vk_surface(&event_loop, instance)?;
+        .with_resizable(false);
+
+        let target = match gpu {
+            Some(gpu) => SurfaceTarget::Vulkan(VulkanSurface::new(gpu, builder.build_vk_surface(&event_loop, gpu.instance.clone())?)?),
+            None => SurfaceTarget::Software(builder.build(&event_loop)?),
+        };
+
+        let surface = CoreSurface {
+            target,
+            widget,
+            cur_mouse_pos: None,
+            las_mouse_pos: None,
+        };
+
+        surface.window().set_outer_position(LogicalPosition::new(surface.widget.position.x(), surface.widget.position.y()));
+
+        if surface.widget.blur_behind {
+            request_blur_behind(surface.window());
+        }
+
+        Ok(surface)
+    }
+
+    pub fn window(&self) -> &Window {
+        match &self.target {
+            SurfaceTarget::Vulkan(v) => v.surface.window(),
+            SurfaceTarget::Software(w) => w,
+        }
+    }
+
+    // Called when the window changed size, the swapchain is made again before the next frame
+    pub fn resized(&mut self) {
+        if let SurfaceTarget::Vulkan(v) = &mut self.target {
+            v.recreate_swapchain = true;
+        }
+    }
+}
+
+/// VulkanSurface is the swapchain of a window and everything drawing into it needs.
+pub struct VulkanSurface {
+    pub surface: Arc<Surface<Window>>,
+    pub swapchain: Arc<Swapchain<Window>>,
+    pub render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    pub samples: u32,
+    pub stencil: Format,
+    pub dynamic_state: DynamicState,
+    pub recreate_swapchain: bool,
+    pub previous_frame_end: Option<Box<(dyn GpuFuture)>>,
+    pub framebuffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
+    pub pipelines: ShapesPipeline,
+}
 
-        surface.window().set_outer_position(LogicalPosition::new(widget.position.x(), widget.position.y()));
+impl VulkanSurface {
+    fn new(gpu: &Gpu, surface: Arc<Surface<Window>>) -> Result<VulkanSurface> {
+        let physical = PhysicalDevice::from_index(&gpu.instance, gpu.physical_index).unwrap();
+        let (device, queue, samples) = (gpu.device.clone(), gpu.queue.clone(), gpu.samples);
 
         let (swapchain, images) = {
-            let caps = surface.capabilities(*physical)?;
+            let caps = surface.capabilities(physical)?;
 
             let alpha = caps.supported_composite_alpha.iter().next().unwrap();
 
-            let format = caps.supported_formats[0].0;
+            let (format, color_space) = surface_format(&caps.supported_formats)?;
 
             let dimensions: [u32; 2] = surface.window().inner_size().into();
 
@@ -233,27 +294,12 @@ impl CoreSurface {
                 PresentMode::Fifo,
                 FullscreenExclusive::Default,
                 true,
-                ColorSpace::SrgbNonLinear,
+                color_space,
             )?
         };
 
-        let render_pass = Arc::new(
-            vulkano::single_pass_renderpass!(
-                device.clone(),
-                attachments: {
-                    color: {
-                        load: Clear,
-                        store: Store,
-                        format: swapchain.format(),
-                        samples: 1,
-                    }
-                },
-                pass: {
-                    color: [color],
-                    depth_stencil: {}
-                }
-            )?,
-        );
+        let stencil = stencil_format(&physical);
+        let render_pass = create_render_pass(device.clone(), swapchain.format(), stencil, samples)?;
 
         let mut dynamic_state = DynamicState {
             line_width: None,
@@ -264,8 +310,14 @@ impl CoreSurface {
             reference: None,
         };
 
-        let framebuffers =
-            window_size_dependent_setup(&images, render_pass.clone(), &mut dynamic_state);
+        let framebuffers = window_size_dependent_setup(
+            device.clone(),
+            &images,
+            render_pass.clone(),
+            stencil,
+            samples,
+            &mut dynamic_state,
+        )?;
 
         let recreate_swapchain = false;
 
@@ -274,19 +326,111 @@ impl CoreSurface {
         let pipelines = ShapesPipeline::new(device.clone(), render_pass.clone())?;
 
         Ok(
-            CoreSurface {
+            VulkanSurface {
                 surface,
                 swapchain,
                 render_pass,
+                samples,
+                stencil,
                 dynamic_state,
                 recreate_swapchain,
                 previous_frame_end,
                 framebuffers,
                 pipelines,
-                widget,
-                cur_mouse_pos: None,
-                las_mouse_pos: None,
             }
         )
     }
-}
\ No newline at end of file
+
+    fn draw(&mut self, gpu: &Gpu, widget: &mut Widget) -> Result<()> {
+        self.previous_frame_end.as_mut().unwrap().cleanup_finished();
+
+        if self.recreate_swapchain {
+            let dimensions: [u32; 2] = self.surface.window().inner_size().into();
+            let (new_swapchain, new_images) = self.swapchain.recreate_with_dimensions(dimensions)?;
+
+            self.swapchain = new_swapchain;
+            self.framebuffers = window_size_dependent_setup(
+                gpu.device.clone(),
+                &new_images,
+                self.render_pass.clone(),
+                self.stencil,
+                self.samples,
+                &mut self.dynamic_state,
+            )?;
+            self.recreate_swapchain = false;
+        }
+
+        let (image_num, suboptimal, acquire_future) =
+            match swapchain::acquire_next_image(self.swapchain.clone(), None) {
+                Ok(r) => r,
+                Err(AcquireError::OutOfDate) => {
+                    self.recreate_swapchain = true;
+                    return Ok(());
+                }
+                Err(e) => return Err(CoreError::from(e)),
+            };
+
+        if suboptimal {
+            self.recreate_swapchain = true;
+        }
+    
+        // Prepare phase, every buffer and texture of the frame is uploaded before recording
+        let mut uploader = VulkanUploader::new(
+            gpu.device.clone(),
+            gpu.queue.clone(),
+            &self.pipelines,
+            widget.bound,
+        );
+
+        widget.draw(&mut uploader)?;
+
+        let uploads = uploader.finish();
+
+        let mut builder = AutoCommandBufferBuilder::primary_one_time_submit(
+            gpu.device.clone(),
+            gpu.queue.family(),
+        )?;
+
+        let mut renderer = VulkanRenderer::new(
+            gpu.device.clone(),
+            gpu.queue.clone(),
+            &mut builder,
+            self.framebuffers[image_num].clone(),
+            &self.pipelines,
+            &self.dynamic_state,
+            widget.bound,
+            self.samples,
+        );
+
+        widget.draw(&mut renderer)?;
+    
+        let command_buffer = builder.build()?;
+    
+        let future = self.previous_frame_end
+            .take()
+            .unwrap()
+            .join(acquire_future)
+            .join(uploads)
+            .then_execute(gpu.queue.clone(), command_buffer)?
+            .then_swapchain_present(gpu.queue.clone(), self.swapchain.clone(), image_num)
+            .then_signal_fence_and_flush();
+    
+        match future {
+            Ok(future) => {
+                self.previous_frame_end = Some(future.boxed());
+                widget.dirty = false;
+            }
+            Err(FlushError::OutOfDate) => {
+                self.recreate_swapchain = true;
+                self.previous_frame_end = Some(sync::now(gpu.device.clone()).boxed());
+            }
+            Err(e) => {
+                self.previous_frame_end = Some(sync::now(gpu.device.clone()).boxed());
+    
+                return Err(CoreError::from(e))
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 use toml;
 use serde_derive::Deserialize;
 use std::fs;
+use std::path::{Path, PathBuf};
 
 use crate::error::Result;
 
@@ -8,7 +9,12 @@ pub fn decode(path: &str) -> Result<WidgetConfig> {
 
     let data = fs::read_to_string(path)?;
 
-    let widget_config: WidgetConfig = toml::from_str(&data)?;
+    let mut widget_config: WidgetConfig = toml::from_str(&data)?;
+
+    widget_config.root = match Path::new(path).parent() {
+        Some(p) => p.to_path_buf(),
+        None => PathBuf::new(),
+    };
 
     Ok(widget_config)
 }
@@ -25,13 +31,72 @@ pub struct WidgetConfig {
     // Position of top left of window on the screen
     pub position: [f32; 2],
 
-    // Hex color value for window background <Optional>
+    // Color of the window background, #rrggbbaa, #rgb, rgb(), hsl() or a CSS name like all colors <Optional>
     pub color: Option<String>,
 
+    // Gradient drawn over the window background <Optional>
+    pub gradient: Option<GradientConfig>,
+
+    // Shadow of the window background, the background is inset to make room for it <Optional>
+    pub shadow: Option<ShadowConfig>,
+
+    // Fades the whole widget, background included, from 0 to 1, defaults to 1 <Optional>
+    pub opacity: Option<f32>,
+
+    // Asks the compositor to blur the desktop seen through the window, X11 compositors
+    // that read the KDE blur hint only, defaults to false <Optional>
+    pub blur_behind: Option<bool>,
+
     // Lits of shapes to be drawn to the WidgetConfig <Optional>
     pub shape: Option<Vec<ShapeWidgetConfig>>,
 
     pub tool: Option<Vec<ToolWidgetConfig>>,
+
+    // Groups of shapes and tools moved and hidden together, drawn after the shapes and tools <Optional>
+    pub group: Option<Vec<GroupConfig>>,
+
+    // Names of the layers from bottom to top, anything without a layer is on "default" <Optional>
+    pub layers: Option<Vec<String>>,
+
+    // Directory of the widget TOML, files used by shapes are relative to it
+    #[serde(skip)]
+    pub root: PathBuf,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GroupConfig {
+    // Name to find the group by at runtime <Optional>
+    pub name: Option<String>,
+
+    // Moves, rotates, scales and skews the group and everything in it <Optional>
+    #[serde(flatten)]
+    pub transform: TransformConfig,
+
+    // Hidden groups are neither drawn nor clickable, defaults to true <Optional>
+    pub visible: Option<bool>,
+
+    // Drawn above siblings with a lower z and hit before them, defaults to 0 <Optional>
+    pub z: Option<i32>,
+
+    // Name of one of the layers of the widget, defaults to the layer of the parent <Optional>
+    pub layer: Option<String>,
+
+    // Shape everything in the group is cut to, in the space of the group <Optional>
+    pub clip: Option<Box<ShapeWidgetConfig>>,
+
+    // Image everything in the group is faded by <Optional>
+    pub mask: Option<MaskConfig>,
+
+    // Fades the group as a whole from 0 to 1, overlapping shapes in it do not show through each other, defaults to 1 <Optional>
+    pub opacity: Option<f32>,
+
+    // Shapes in the group, positioned relative to its offset <Optional>
+    pub shape: Option<Vec<ShapeWidgetConfig>>,
+
+    pub tool: Option<Vec<ToolWidgetConfig>>,
+
+    // Nested groups, drawn after the shapes and tools of this one <Optional>
+    pub group: Option<Vec<GroupConfig>>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -39,34 +104,318 @@ pub struct ShapeWidgetConfig {
     // Type of shape to be drawn <Non-Optional>
     pub shape_type: Shapes,
 
+    // Name to find the shape by at runtime <Optional>
+    pub name: Option<String>,
+
+    // Hidden shapes are not drawn, defaults to true <Optional>
+    pub visible: Option<bool>,
+
+    // Drawn above siblings with a lower z and hit before them, defaults to 0 <Optional>
+    pub z: Option<i32>,
+
+    // Name of one of the layers of the widget, defaults to the layer of the parent <Optional>
+    pub layer: Option<String>,
+
+    // Moves, rotates, scales and skews the shape <Optional>
+    #[serde(flatten)]
+    pub transform: TransformConfig,
+
+    #[serde(default)]
     pub shape: Vec<f32>,
 
-    // Hex color value for shape background <Optional>
+    // SVG path data, only used by Path <Optional>
+    pub path: Option<String>,
+
+    // Color value for shape background <Optional>
     pub color: Option<String>,
 
+    // Gradient filling the shape instead of color <Optional>
+    pub gradient: Option<GradientConfig>,
+
+    // How the shape is drawn onto what is beneath it, defaults to Normal <Optional>
+    pub blend: Option<Blend>,
+
+    // Drop shadow drawn beneath the shape <Optional>
+    pub shadow: Option<ShadowConfig>,
+
+    // Gaussian blur radius in pixels applied to the shape itself <Optional>
+    pub blur: Option<f32>,
+
+    // Gaussian blur radius in pixels of what is drawn behind the shape, seen through it <Optional>
+    pub backdrop_blur: Option<f32>,
+
+    // Whether the shape is filled, outlined or both, and how the outline looks <Optional>
+    #[serde(flatten)]
+    pub format: FormatConfig,
+
+    // Corner radius of a Rectangle, one value or top left, top right, bottom right, bottom left <Optional>
+    pub radius: Option<Radius>,
+
+    // String drawn by Text <Optional>
+    pub text: Option<String>,
+
+    // TTF or OTF file used by Text, relative to the widget TOML <Optional>
+    pub font: Option<String>,
+
+    // Pixel height of Text, defaults to 16 <Optional>
+    pub font_size: Option<f32>,
+
+    // Left (default), Center or Right <Optional>
+    pub align: Option<Align>,
+
+    // Width at which Text wraps onto a new line, no wrapping by default <Optional>
+    pub wrap: Option<f32>,
+
+    // PNG or JPEG file drawn by Image, relative to the widget TOML <Optional>
+    pub image: Option<String>,
+
+    // How Image is scaled into its rectangle, defaults to Fit <Optional>
+    pub scaling: Option<Scaling>,
+
+    // Opacity of Image from 0 to 1, defaults to 1 <Optional>
+    pub opacity: Option<f32>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FormatConfig {
+    // Fill (default), Line or FillLine <Optional>
     pub format: Option<Format>,
+
+    // Width of the outline drawn by Line and FillLine, defaults to 1 <Optional>
+    pub line_width: Option<f32>,
+
+    // How outline segments are joined, defaults to Miter <Optional>
+    pub line_join: Option<Join>,
+
+    // How the ends of open outlines are drawn, defaults to Butt <Optional>
+    pub line_cap: Option<Cap>,
+
+    // Color value for the outline, defaults to color, or black when there is none <Optional>
+    pub line_color: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TransformConfig {
+    // Distance the node is moved by, defaults to [0, 0] <Optional>
+    pub offset: Option<[f32; 2]>,
+
+    // Clockwise rotation around the origin in degrees <Optional>
+    pub rotate: Option<f32>,
+
+    // Scale along x and y around the origin, defaults to [1, 1] <Optional>
+    pub scale: Option<[f32; 2]>,
+
+    // Skew angles along x and y around the origin in degrees, defaults to [0, 0] <Optional>
+    pub skew: Option<[f32; 2]>,
+
+    // Point rotate, scale and skew are around, "center" or [x, y], defaults to [0, 0] <Optional>
+    pub origin: Option<Origin>,
+}
+
+#[derive(Debug, Copy, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Origin {
+    Point([f32; 2]),
+    Anchor(Anchor),
+}
+
+#[derive(Debug, Copy, Clone, Deserialize)]
+pub enum Anchor {
+    // Center of the shape, or of the bounds of everything in a group
+    #[serde(alias = "center")]
+    Center,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MaskConfig {
+    // PNG or JPEG file, relative to the widget TOML <Non-Optional>
+    pub image: String,
+
+    // Alpha (default) or Luminance, what of the image makes the group visible <Optional>
+    pub mode: Option<MaskModeConfig>,
+
+    // Rectangle the image is stretched over, x, y, width and height, defaults to the bounds of the group <Optional>
+    pub rect: Option<[f32; 4]>,
+}
+
+#[derive(Debug, Copy, Clone, Deserialize)]
+pub enum MaskModeConfig {
+    Alpha,
+    Luminance,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ShadowConfig {
+    // Distance the shadow is moved by, defaults to [0, 0] <Optional>
+    pub offset: Option<[f32; 2]>,
+
+    // Blur radius in pixels, defaults to 4 <Optional>
+    pub blur: Option<f32>,
+
+    // Color value of the shadow, defaults to #00000080 <Optional>
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GradientConfig {
+    // Linear or Radial <Non-Optional>
+    pub ty: GradientType,
+
+    // Direction of a Linear gradient in degrees, 0 is left to right and 90 top to bottom <Optional>
+    pub angle: Option<f32>,
+
+    // Center of a Radial gradient as a fraction of the bounds, defaults to [0.5, 0.5] <Optional>
+    pub center: Option<[f32; 2]>,
+
+    // Radius of a Radial gradient as a fraction of the larger half of the bounds, defaults to 1 <Optional>
+    pub radius: Option<f32>,
+
+    // Colors along the gradient, at least two <Non-Optional>
+    pub stops: Vec<StopConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StopConfig {
+    pub color: String,
+
+    // Position from 0 to 1, stops without one are spread evenly <Optional>
+    pub offset: Option<f32>,
+}
+
+#[derive(Debug, Copy, Clone, Deserialize)]
+pub enum GradientType {
+    Linear,
+    Radial,
+}
+
+#[derive(Debug, Copy, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Radius {
+    Uniform(f32),
+    Corners([f32; 4]),
+}
+
+impl Radius {
+    pub fn corners(&self) -> [f32; 4] {
+        match *self {
+            Radius::Uniform(r) => [r; 4],
+            Radius::Corners(c) => c,
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, Deserialize)]
 pub enum Shapes {
+    // shape = [xa, ya, xb, yb, xc, yc]
     Triangle,
+    // shape = [x, y, width, height]
     Rectangle,
+    // shape = [center x, center y, radius]
+    Circle,
+    // shape = [center x, center y, radius x, radius y]
+    Ellipse,
+    // shape = [x0, y0, x1, y1, x2, y2, ...], closed and filled
+    Polygon,
+    // shape = [x0, y0, x1, y1, ...], open and always stroked
+    Polyline,
+    // shape = [], [x, y] or [x, y, scale], drawn from the path field
+    Path,
+    // shape = [x, y], top left of the text
+    Text,
+    // shape = [x, y, width, height]
+    Image,
 }
 
 #[derive(Debug, Copy, Clone, Deserialize)]
 pub enum Format {
     Fill,
     Line,
+    FillLine,
+}
+
+#[derive(Debug, Copy, Clone, Deserialize)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Copy, Clone, Deserialize)]
+pub enum Scaling {
+    Fit,
+    Fill,
+    Stretch,
+    Tile,
+}
+
+#[derive(Debug, Copy, Clone, Deserialize)]
+pub enum Blend {
+    Normal,
+    Additive,
+    // Assumes what is beneath is opaque, over transparent pixels it turns black
+    Multiply,
+    Screen,
+}
+
+#[derive(Debug, Copy, Clone, Deserialize)]
+pub enum Join {
+    Miter,
+    Round,
+    Bevel,
+}
+
+#[derive(Debug, Copy, Clone, Deserialize)]
+pub enum Cap {
+    Butt,
+    Round,
+    Square,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct ToolWidgetConfig {
     pub ty: Tools,
 
+    // Name to find the tool by at runtime <Optional>
+    pub name: Option<String>,
+
+    // Hidden tools are neither drawn nor clickable, defaults to true <Optional>
+    pub visible: Option<bool>,
+
+    // Drawn above siblings with a lower z and hit before them, defaults to 0 <Optional>
+    pub z: Option<i32>,
+
+    // Name of one of the layers of the widget, defaults to the layer of the parent <Optional>
+    pub layer: Option<String>,
+
+    // Moves, rotates, scales and skews the tool, it stays clickable where it is drawn <Optional>
+    #[serde(flatten)]
+    pub transform: TransformConfig,
+
+    // Type of shape used for the tool, defaults to Rectangle <Optional>
+    pub shape_type: Option<Shapes>,
+
+    #[serde(default)]
     pub shape: Vec<f32>,
 
+    pub path: Option<String>,
+
     pub color: Option<String>,
 
+    // Gradient filling the tool instead of color <Optional>
+    pub gradient: Option<GradientConfig>,
+
+    // Whether the tool is filled, outlined or both, and how the outline looks <Optional>
+    #[serde(flatten)]
+    pub format: FormatConfig,
+
+    // Color of the tool while the cursor is over it <Optional>
+    pub hover_color: Option<String>,
+
+    // How the tool is drawn onto what is beneath it, defaults to Normal <Optional>
+    pub blend: Option<Blend>,
+
+    pub radius: Option<Radius>,
+
     pub action: Option<Action>,
 }
 
@@ -0,0 +1,323 @@
+use crate::render::Renderer;
+use crate::software::SoftwareRenderer;
+use crate::shape::{Shape, ShapeFormat};
+use crate::buffer::{TexturedBuffer, TexturedVertex};
+use crate::gradient::Gradient;
+use crate::vector::Vector;
+use crate::color::{self, Color};
+use crate::frame::Frame;
+use crate::error::Result;
+
+/// Shadow is a blurred, offset and tinted copy of a shape drawn beneath it.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Shadow {
+    pub offset: Vector,
+
+    // Blur radius in pixels, the shadow grows this far past the shape
+    pub blur: f32,
+
+    pub color: Color,
+}
+
+/// Layer is part of the widget rendered offscreen and blurred, ready to be drawn as a texture.
+#[derive(Debug, Clone)]
+pub struct Layer {
+    // Top left of the image in widget space
+    pub position: Vector,
+
+    // Premultiplied pixels
+    pub image: Frame,
+}
+
+impl Layer {
+    /// Renders shape alone into an offscreen image the size of the widget, then crops
+    /// it to what was drawn and blurs it by radius.
+    pub fn render(shape: &mut dyn Shape, resolution: Vector, radius: f32) -> Result<Layer> {
+        let mut offscreen = SoftwareRenderer::new(resolution.x() as u32, resolution.y() as u32);
+
+        offscreen.begin(Color::default())?;
+        shape.draw(&mut offscreen)?;
+        offscreen.end()?;
+
+        Ok(Layer::crop(&offscreen, radius))
+    }
+
+    /// Crops what was drawn into offscreen, grown by the reach of the blur, and blurs it
+    /// by radius. The position is in the pixels of offscreen.
+    pub fn crop(offscreen: &SoftwareRenderer, radius: f32) -> Layer {
+        let (width, height) = (offscreen.width, offscreen.height);
+
+        // Bounds of the drawn pixels, grown by the reach of the blur
+        let mut min = (width, height);
+        let mut max = (0u32, 0u32);
+
+        for y in 0..height {
+            for x in 0..width {
+                if offscreen.pixels[(y * width + x) as usize][3] > 0f32 {
+                    min = (min.0.min(x), min.1.min(y));
+                    max = (max.0.max(x + 1), max.1.max(y + 1));
+                }
+            }
+        }
+
+        if min.0 >= max.0 {
+            return Layer {
+                position: Vector::default(),
+                image: Frame::new(1, 1, vec![0u8; 4]),
+            };
+        }
+
+        let margin = radius.ceil().max(0f32) as u32;
+        let (x0, y0) = (min.0.saturating_sub(margin), min.1.saturating_sub(margin));
+        let (x1, y1) = ((max.0 + margin).min(width), (max.1 + margin).min(height));
+        let (w, h) = (x1 - x0, y1 - y0);
+
+        let mut pixels = Vec::with_capacity((w * h) as usize);
+        for y in y0..y1 {
+            pixels.extend_from_slice(&offscreen.pixels[(y * width + x0) as usize..(y * width + x1) as usize]);
+        }
+
+        blur(&mut pixels, w, h, radius);
+
+        // Stored the way textures are, the shaders take it back into linear light
+        let data = pixels
+            .iter()
+            .flat_map(|&px| color::encode_pixel(px).to_vec())
+            .collect();
+
+        Layer {
+            position: Vector::new(x0 as f32, y0 as f32),
+            image: Frame::new(w, h, data),
+        }
+    }
+
+    // Keeps only the coverage, as premultiplied white, so the layer can be tinted
+    pub fn to_coverage(&mut self) {
+        for px in self.image.data.chunks_mut(4) {
+            let a = px[3];
+            px.copy_from_slice(&[a, a, a, a]);
+        }
+    }
+
+    // Draws the layer moved by offset, every texel multiplied with color
+    pub fn draw(&self,
+        renderer: &mut dyn Renderer,
+        buffer: &mut Option<TexturedBuffer>,
+        offset: Vector,
+        color: Color,
+    ) -> Result<()> {
+        // Vertices sit on pixel centers, move back half a pixel to line the quad up with the pixel edges
+        let min = self.position + offset - 0.5f32;
+        let max = min + Vector::new(self.image.width as f32, self.image.height as f32);
+
+        let vertices = [
+            TexturedVertex::new(min.position, [0f32, 0f32]),
+            TexturedVertex::new([max.x(), min.y()], [1f32, 0f32]),
+            TexturedVertex::new([min.x(), max.y()], [0f32, 1f32]),
+            TexturedVertex::new(max.position, [1f32, 1f32]),
+        ];
+
+        renderer.textured(buffer, &vertices, &[0u32, 1u32, 2u32, 1u32, 3u32, 2u32], &self.image, false, color)
+    }
+}
+
+/// Weights of a normalized Gaussian reaching radius pixels to each side, sigma being half the radius.
+pub fn gaussian_kernel(radius: f32) -> Vec<f32> {
+    let n = radius.ceil().max(0f32) as i32;
+    let sigma = (radius / 2f32).max(0.5f32);
+
+    let weights: Vec<f32> = (-n..=n)
+        .map(|x| (-(x * x) as f32 / (2f32 * sigma * sigma)).exp())
+        .collect();
+
+    let sum: f32 = weights.iter().sum();
+
+    weights.iter().map(|w| w / sum).collect()
+}
+
+/// Separable Gaussian blur of premultiplied pixels, horizontally then vertically.
+/// Everything outside the image counts as transparent.
+pub fn blur(pixels: &mut Vec<[f32; 4]>, width: u32, height: u32, radius: f32) {
+    if radius <= 0f32 {
+        return;
+    }
+
+    let kernel = gaussian_kernel(radius);
+    let n = (kernel.len() / 2) as i64;
+    let (w, h) = (width as i64, height as i64);
+
+    let pass = |src: &Vec<[f32; 4]>, horizontal: bool| -> Vec<[f32; 4]> {
+        let mut dst = vec![[0f32; 4]; src.len()];
+
+        for y in 0..h {
+            for x in 0..w {
+                let mut sum = [0f32; 4];
+
+                for (k, weight) in kernel.iter().enumerate() {
+                    let d = k as i64 - n;
+                    let (sx, sy) = if horizontal { (x + d, y) } else { (x, y + d) };
+
+                    if sx < 0 || sy < 0 || sx >= w || sy >= h {
+                        continue;
+                    }
+
+                    let px = src[(sy * w + sx) as usize];
+                    for (s, p) in sum.iter_mut().zip(px.iter()) {
+                        *s += p * weight;
+                    }
+                }
+
+                dst[(y * w + x) as usize] = sum;
+            }
+        }
+
+        dst
+    };
+
+    let horizontal = pass(pixels, true);
+    *pixels = pass(&horizontal, false);
+}
+
+/// Effects wraps a shape to draw it with a drop shadow, blurred, or both.
+/// The offscreen passes run on the first draw and are kept until the shape changes.
+#[derive(Debug)]
+pub struct Effects {
+    pub shape: Box<dyn Shape>,
+
+    pub shadow: Option<Shadow>,
+
+    // Blur radius of the shape itself, 0 draws it sharp
+    pub blur: f32,
+
+    pub shadow_layer: Option<Layer>,
+    pub shadow_buffer: Option<TexturedBuffer>,
+
+    pub blur_layer: Option<Layer>,
+    pub blur_buffer: Option<TexturedBuffer>,
+
+    // The shadow and blur the layers were baked with, the public fields can change after that
+    baked_shadow: Option<Shadow>,
+    baked_blur: f32,
+}
+
+impl Effects {
+    pub fn new(shape: Box<dyn Shape>, shadow: Option<Shadow>, blur: f32) -> Effects {
+        Effects {
+            shape,
+            shadow,
+            blur,
+
+            shadow_layer: None,
+            shadow_buffer: None,
+
+            blur_layer: None,
+            blur_buffer: None,
+
+            baked_shadow: shadow,
+            baked_blur: blur,
+        }
+    }
+
+    // Drops the baked layers, so they are rendered again on the next draw
+    fn invalidate(&mut self) {
+        self.shadow_layer = None;
+        self.shadow_buffer = None;
+        self.blur_layer = None;
+        self.blur_buffer = None;
+    }
+}
+
+impl Shape for Effects {
+    fn center(&self) -> Vector {
+        self.shape.center()
+    }
+    fn area(&self) -> f32 {
+        self.shape.area()
+    }
+
+    fn outline(&self) -> Vec<Vector> {
+        self.shape.outline()
+    }
+
+    fn color(&mut self, c: Color) {
+        self.shape.color(c);
+        self.invalidate();
+    }
+    fn gradient(&mut self, g: Gradient) {
+        self.shape.gradient(g);
+        self.invalidate();
+    }
+    fn format(&mut self, f: ShapeFormat) {
+        self.shape.format(f);
+        self.invalidate();
+    }
+
+    fn contains(&self, v: Vector) -> bool {
+        self.shape.contains(v)
+    }
+
+    fn draw(&mut self, renderer: &mut dyn Renderer) -> Result<()> {
+        let resolution = renderer.resolution();
+
+        if self.shadow != self.baked_shadow || self.blur != self.baked_blur {
+            self.invalidate();
+            self.baked_shadow = self.shadow;
+            self.baked_blur = self.blur;
+        }
+
+        if let Some(shadow) = self.shadow {
+            if self.shadow_layer.is_none() {
+                let mut layer = Layer::render(&mut *self.shape, resolution, shadow.blur)?;
+                layer.to_coverage();
+
+                self.shadow_layer = Some(layer);
+            }
+
+            if let Some(layer) = &self.shadow_layer {
+                layer.draw(renderer, &mut self.shadow_buffer, shadow.offset, shadow.color)?;
+            }
+        }
+
+        if self.blur <= 0f32 {
+            return self.shape.draw(renderer);
+        }
+
+        if self.blur_layer.is_none() {
+            self.blur_layer = Some(Layer::render(&mut *self.shape, resolution, self.blur)?);
+        }
+
+        match &self.blur_layer {
+            Some(layer) => layer.draw(renderer, &mut self.blur_buffer, Vector::default(), Color::new(u8::MAX, u8::MAX, u8::MAX, u8::MAX)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::Rectangle;
+
+    #[test]
+    fn shadow_changes_bake_the_layer_again() {
+        let shadow = Shadow {
+            offset: Vector::new(0f32, 2f32),
+            blur: 2f32,
+            color: Color::new(0, 0, 0, u8::MAX),
+        };
+
+        let mut rect = Rectangle::new(20f32, 20f32, 20f32, 20f32);
+        rect.color(Color::new(255, 0, 0, u8::MAX));
+
+        let mut effects = Effects::new(Box::new(rect), Some(shadow), 0f32);
+        let mut renderer = SoftwareRenderer::new(80, 80);
+
+        effects.draw(&mut renderer).unwrap();
+        let small = effects.shadow_layer.as_ref().unwrap().image.width;
+
+        effects.shadow = Some(Shadow { blur: 8f32, ..shadow });
+        effects.draw(&mut renderer).unwrap();
+
+        assert!(effects.shadow_layer.as_ref().unwrap().image.width > small);
+    }
+}
@@ -8,12 +8,21 @@ use vulkano::framebuffer::{FramebufferCreationError, RenderPassCreationError};
 use vulkano::device::DeviceCreationError;
 use vulkano::swapchain::{SwapchainCreationError, AcquireError, CapabilitiesError};
 use vulkano::OomError;
-use vulkano::command_buffer::{BuildError, CommandBufferExecError, BeginRenderPassError, DrawError};
+use vulkano::command_buffer::{BuildError, CommandBufferExecError, BeginRenderPassError, DrawError, CopyBufferImageError, AutoCommandBufferBuilderContextError};
 use vulkano::sync::FlushError;
 use vulkano_win::CreationError;
 use vulkano::pipeline::GraphicsPipelineCreationError;
 use vulkano::memory::DeviceMemoryAllocError;
+use vulkano::image::ImageCreationError;
+use vulkano::buffer::cpu_access::ReadLockError;
+use png::{EncodingError, DecodingError};
 use glob::{PatternError, GlobError};
+use jpeg_decoder::Error as JpegError;
+use winit::error::OsError;
+use vulkano::sampler::SamplerCreationError;
+use vulkano::descriptor::descriptor_set::{PersistentDescriptorSetError, PersistentDescriptorSetBuildError};
+
+use crate::util::DeviceSelector;
 
 
 pub type Result<T> = std::result::Result<T, CoreError>;
@@ -22,8 +31,19 @@ pub type Result<T> = std::result::Result<T, CoreError>;
 pub enum CoreError {
     ValidationFail,
     InvalidShapeFormat,
+    InvalidPathData,
     NoSupportedPhysicalDevice,
+    PhysicalDeviceNotFound(DeviceSelector),
     Unimplemented,
+    UnsupportedImageFormat,
+    InvalidFont,
+    InvalidGradient(String),
+    NodeNotFound(usize),
+    LayerNotFound(String),
+    InvalidColor(String, String),
+    NoSrgbSurfaceFormat,
+    WidgetTooSmall(f32),
+    PresentUnsupported,
     TomlError(de::Error),
     IoError(io::Error),
     FromHexError(FromHexError),
@@ -45,6 +65,17 @@ pub enum CoreError {
     DeviceMemoryAllocError(DeviceMemoryAllocError),
     PatternError(PatternError),
     GlobError(GlobError),
+    ImageCreationError(ImageCreationError),
+    CopyBufferImageError(CopyBufferImageError),
+    AutoCommandBufferBuilderContextError(AutoCommandBufferBuilderContextError),
+    ReadLockError(ReadLockError),
+    EncodingError(EncodingError),
+    DecodingError(DecodingError),
+    SamplerCreationError(SamplerCreationError),
+    PersistentDescriptorSetError(PersistentDescriptorSetError),
+    PersistentDescriptorSetBuildError(PersistentDescriptorSetBuildError),
+    JpegError(JpegError),
+    OsError(OsError),
 }
 
 impl fmt::Display for CoreError {
@@ -54,10 +85,34 @@ impl fmt::Display for CoreError {
                 write!(f, "validation failed during decoding of WidgetConfig"),
             CoreError::InvalidShapeFormat =>
                 write!(f, "the provided values to shape are incorrect"),
+            CoreError::InvalidPathData =>
+                write!(f, "the provided path data is not valid SVG path syntax"),
             CoreError::NoSupportedPhysicalDevice =>
                 write!(f, "no supported physical device found"),
+            CoreError::PhysicalDeviceNotFound(DeviceSelector::Index(i)) =>
+                write!(f, "no supported physical device with index {} found", i),
+            CoreError::PhysicalDeviceNotFound(DeviceSelector::Name(ref n)) =>
+                write!(f, "no supported physical device matching \"{}\" found", n),
             CoreError::Unimplemented =>
                 write!(f, "function is unimplemented"),
+            CoreError::UnsupportedImageFormat =>
+                write!(f, "image uses an unsupported pixel format"),
+            CoreError::InvalidFont =>
+                write!(f, "font file could not be parsed as TrueType or OpenType"),
+            CoreError::InvalidGradient(ref reason) =>
+                write!(f, "invalid gradient: {}", reason),
+            CoreError::NodeNotFound(id) =>
+                write!(f, "no node with id {} in the scene", id),
+            CoreError::LayerNotFound(ref name) =>
+                write!(f, "layer {} is not in the layers of the widget", name),
+            CoreError::InvalidColor(ref value, ref reason) =>
+                write!(f, "invalid color \"{}\": {}", value, reason),
+            CoreError::NoSrgbSurfaceFormat =>
+                write!(f, "the surface offers no sRGB format with the sRGB color space"),
+            CoreError::WidgetTooSmall(inset) =>
+                write!(f, "the widget is too small for the shadow of its background, which is inset by {} pixels on every side", inset),
+            CoreError::PresentUnsupported =>
+                write!(f, "the software backend can only show frames in X11 windows"),
             CoreError::TomlError(ref e) => e.fmt(f),
             CoreError::IoError(ref e) => e.fmt(f),
             CoreError::FromHexError(ref e) => e.fmt(f),
@@ -79,6 +134,17 @@ impl fmt::Display for CoreError {
             CoreError::DeviceMemoryAllocError(ref e) => e.fmt(f),
             CoreError::PatternError(ref e) => e.fmt(f),
             CoreError::GlobError(ref e) => e.fmt(f),
+            CoreError::ImageCreationError(ref e) => e.fmt(f),
+            CoreError::CopyBufferImageError(ref e) => e.fmt(f),
+            CoreError::AutoCommandBufferBuilderContextError(ref e) => e.fmt(f),
+            CoreError::ReadLockError(ref e) => e.fmt(f),
+            CoreError::EncodingError(ref e) => e.fmt(f),
+            CoreError::DecodingError(ref e) => e.fmt(f),
+            CoreError::SamplerCreationError(ref e) => e.fmt(f),
+            CoreError::PersistentDescriptorSetError(ref e) => e.fmt(f),
+            CoreError::PersistentDescriptorSetBuildError(ref e) => e.fmt(f),
+            CoreError::JpegError(ref e) => e.fmt(f),
+            CoreError::OsError(ref e) => e.fmt(f),
         }
     }
 }
@@ -88,8 +154,19 @@ impl error::Error for CoreError {
         match *self {
             CoreError::ValidationFail => None,
             CoreError::InvalidShapeFormat => None,
+            CoreError::InvalidPathData => None,
             CoreError::NoSupportedPhysicalDevice => None,
+            CoreError::PhysicalDeviceNotFound(_) => None,
             CoreError::Unimplemented => None,
+            CoreError::UnsupportedImageFormat => None,
+            CoreError::InvalidFont => None,
+            CoreError::InvalidGradient(_) => None,
+            CoreError::NodeNotFound(_) => None,
+            CoreError::LayerNotFound(_) => None,
+            CoreError::InvalidColor(_, _) => None,
+            CoreError::NoSrgbSurfaceFormat => None,
+            CoreError::WidgetTooSmall(_) => None,
+            CoreError::PresentUnsupported => None,
             CoreError::TomlError(ref e) => Some(e),
             CoreError::IoError(ref e) => Some(e),
             CoreError::FromHexError(ref e) => Some(e),
@@ -111,6 +188,17 @@ impl error::Error for CoreError {
             CoreError::DeviceMemoryAllocError(ref e) => Some(e),
             CoreError::PatternError(ref e) => Some(e),
             CoreError::GlobError(ref e) => Some(e),
+            CoreError::ImageCreationError(ref e) => Some(e),
+            CoreError::CopyBufferImageError(ref e) => Some(e),
+            CoreError::AutoCommandBufferBuilderContextError(ref e) => Some(e),
+            CoreError::ReadLockError(ref e) => Some(e),
+            CoreError::EncodingError(ref e) => Some(e),
+            CoreError::DecodingError(ref e) => Some(e),
+            CoreError::SamplerCreationError(ref e) => Some(e),
+            CoreError::PersistentDescriptorSetError(ref e) => Some(e),
+            CoreError::PersistentDescriptorSetBuildError(ref e) => Some(e),
+            CoreError::JpegError(ref e) => Some(e),
+            CoreError::OsError(ref e) => Some(e),
         }
     }
 }
@@ -241,3 +329,68 @@ impl From<GlobError> for CoreError {
     }
 }
 
+impl From<ImageCreationError> for CoreError {
+    fn from(err: ImageCreationError) -> CoreError {
+        CoreError::ImageCreationError(err)
+    }
+}
+
+impl From<CopyBufferImageError> for CoreError {
+    fn from(err: CopyBufferImageError) -> CoreError {
+        CoreError::CopyBufferImageError(err)
+    }
+}
+
+impl From<AutoCommandBufferBuilderContextError> for CoreError {
+    fn from(err: AutoCommandBufferBuilderContextError) -> CoreError {
+        CoreError::AutoCommandBufferBuilderContextError(err)
+    }
+}
+
+impl From<ReadLockError> for CoreError {
+    fn from(err: ReadLockError) -> CoreError {
+        CoreError::ReadLockError(err)
+    }
+}
+
+impl From<EncodingError> for CoreError {
+    fn from(err: EncodingError) -> CoreError {
+        CoreError::EncodingError(err)
+    }
+}
+
+impl From<DecodingError> for CoreError {
+    fn from(err: DecodingError) -> CoreError {
+        CoreError::DecodingError(err)
+    }
+}
+
+impl From<SamplerCreationError> for CoreError {
+    fn from(err: SamplerCreationError) -> CoreError {
+        CoreError::SamplerCreationError(err)
+    }
+}
+
+impl From<PersistentDescriptorSetError> for CoreError {
+    fn from(err: PersistentDescriptorSetError) -> CoreError {
+        CoreError::PersistentDescriptorSetError(err)
+    }
+}
+
+impl From<PersistentDescriptorSetBuildError> for CoreError {
+    fn from(err: PersistentDescriptorSetBuildError) -> CoreError {
+        CoreError::PersistentDescriptorSetBuildError(err)
+    }
+}
+
+impl From<JpegError> for CoreError {
+    fn from(err: JpegError) -> CoreError {
+        CoreError::JpegError(err)
+    }
+}
+
+impl From<OsError> for CoreError {
+    fn from(err: OsError) -> CoreError {
+        CoreError::OsError(err)
+    }
+}
@@ -0,0 +1,139 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use crate::color;
+use crate::error::{CoreError, Result};
+
+/// Frame is a CPU side RGBA8 image, used as the output of offscreen rendering.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+
+    pub data: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(width: u32, height: u32, data: Vec<u8>) -> Frame {
+        Frame {
+            width,
+            height,
+            data,
+        }
+    }
+
+    // Returns the RGBA value of the pixel at x, y
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * self.width + x) * 4) as usize;
+        [self.data[i], self.data[i + 1], self.data[i + 2], self.data[i + 3]]
+    }
+
+    // Converts the premultiplied colors written to the sRGB attachments back to straight alpha,
+    // which is what png expects. Every channel was encoded on its own, so the division happens
+    // in linear light.
+    pub fn unpremultiply(&mut self) {
+        for px in self.data.chunks_mut(4) {
+            let a = px[3] as f32 / u8::MAX as f32;
+            if a > 0f32 {
+                for c in px[..3].iter_mut() {
+                    let linear = color::srgb_to_linear(*c as f32 / u8::MAX as f32) / a;
+                    *c = (color::linear_to_srgb(linear) * u8::MAX as f32).round() as u8;
+                }
+            }
+        }
+    }
+
+    // Converts straight alpha to the premultiplied colors expected by Renderer::textured
+    pub fn premultiply(&mut self) {
+        for px in self.data.chunks_mut(4) {
+            let a = px[3] as f32 / u8::MAX as f32;
+
+            px[0] = (px[0] as f32 * a).round() as u8;
+            px[1] = (px[1] as f32 * a).round() as u8;
+            px[2] = (px[2] as f32 * a).round() as u8;
+        }
+    }
+
+    // Loads a png or jpeg, picked by the file extension
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Frame> {
+        let extension = path.as_ref()
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+
+        match extension.as_ref().map(|e| e.as_str()) {
+            Some("png") => Frame::load_png(path),
+            Some("jpg") | Some("jpeg") => Frame::load_jpeg(path),
+            _ => Err(CoreError::UnsupportedImageFormat),
+        }
+    }
+
+    pub fn load_jpeg<P: AsRef<Path>>(path: P) -> Result<Frame> {
+        let mut decoder = jpeg_decoder::Decoder::new(BufReader::new(File::open(path)?));
+
+        let buf = decoder.decode()?;
+        let info = decoder.info().ok_or(CoreError::UnsupportedImageFormat)?;
+
+        let data = match info.pixel_format {
+            jpeg_decoder::PixelFormat::RGB24 => buf
+                .chunks(3)
+                .flat_map(|px| vec![px[0], px[1], px[2], u8::MAX])
+                .collect(),
+            jpeg_decoder::PixelFormat::L8 => buf
+                .iter()
+                .flat_map(|&px| vec![px, px, px, u8::MAX])
+                .collect(),
+            jpeg_decoder::PixelFormat::CMYK32 => return Err(CoreError::UnsupportedImageFormat),
+        };
+
+        Ok(Frame::new(info.width as u32, info.height as u32, data))
+    }
+
+    pub fn load_png<P: AsRef<Path>>(path: P) -> Result<Frame> {
+        let mut decoder = png::Decoder::new(File::open(path)?);
+        decoder.set_transformations(png::Transformations::EXPAND);
+
+        let (info, mut reader) = decoder.read_info()?;
+
+        let mut buf = vec![0u8; info.buffer_size()];
+        reader.next_frame(&mut buf)?;
+
+        if info.bit_depth != png::BitDepth::Eight {
+            return Err(CoreError::UnsupportedImageFormat);
+        }
+
+        let data = match info.color_type {
+            png::ColorType::RGBA => buf,
+            png::ColorType::RGB => buf
+                .chunks(3)
+                .flat_map(|px| vec![px[0], px[1], px[2], u8::MAX])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => buf
+                .chunks(2)
+                .flat_map(|px| vec![px[0], px[0], px[0], px[1]])
+                .collect(),
+            png::ColorType::Grayscale => buf
+                .iter()
+                .flat_map(|&px| vec![px, px, px, u8::MAX])
+                .collect(),
+            png::ColorType::Indexed => return Err(CoreError::UnsupportedImageFormat),
+        };
+
+        Ok(Frame::new(info.width, info.height, data))
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let file = File::create(path)?;
+        let w = BufWriter::new(file);
+
+        let mut encoder = png::Encoder::new(w, self.width, self.height);
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.data)?;
+
+        Ok(())
+    }
+}
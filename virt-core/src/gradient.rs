@@ -0,0 +1,210 @@
+use crate::vector::Vector;
+use crate::color::Color;
+use crate::frame::Frame;
+use crate::error::{CoreError, Result};
+
+// Number of texels the stops are baked into
+pub const RAMP_SIZE: u32 = 256;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum GradientKind {
+    // Angle in degrees, 0 runs left to right and 90 top to bottom
+    Linear { angle: f32 },
+    // Center as a fraction of the bounds, radius as a fraction of the larger half extent
+    Radial { center: Vector, radius: f32 },
+}
+
+/// Gradient is a multi stop color ramp, laid out over the bounds of whatever it fills.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradient {
+    pub kind: GradientKind,
+
+    // Offsets from 0 to 1 in increasing order
+    pub stops: Vec<(f32, Color)>,
+}
+
+/// GradientSpan is a gradient placed in widget space, t runs from 0 at start to 1 at end.
+/// For radial gradients start is the center and end.x() the radius.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct GradientSpan {
+    pub start: Vector,
+    pub end: Vector,
+    pub radial: bool,
+}
+
+impl GradientSpan {
+    pub fn t(&self, p: Vector) -> f32 {
+        let t = if self.radial {
+            (p - self.start).hypot() / self.end.x()
+        } else {
+            let d = self.end - self.start;
+            (p - self.start).dot(d) / d.dot(d)
+        };
+
+        t.max(0f32).min(1f32)
+    }
+}
+
+impl Gradient {
+    /// Takes at least two stops with offsets from 0 to 1, in any order.
+    pub fn new(kind: GradientKind, mut stops: Vec<(f32, Color)>) -> Result<Gradient> {
+        if stops.len() < 2 {
+            return Err(CoreError::InvalidGradient(format!("{} stops given, at least 2 are needed", stops.len())));
+        }
+
+        if let Some((offset, _)) = stops.iter().find(|(o, _)| !(0f32..=1f32).contains(o)) {
+            return Err(CoreError::InvalidGradient(format!("stop offset {} is not from 0 to 1", offset)));
+        }
+
+        // NaN is outside of 0 to 1, so every offset compares
+        stops.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        Ok(Gradient {
+            kind,
+            stops,
+        })
+    }
+
+    // Places the gradient over the rectangle from min to max
+    pub fn span(&self, min: Vector, max: Vector) -> GradientSpan {
+        let center = (min + max) / 2f32;
+        let half = (max - min) / 2f32;
+
+        match self.kind {
+            GradientKind::Linear { angle } => {
+                let (sin, cos) = angle.to_radians().sin_cos();
+
+                // Long enough for the corners to land on the first and last stop, like CSS
+                let reach = (half.x() * cos).abs() + (half.y() * sin).abs();
+                let dir = Vector::new(cos, sin) * reach;
+
+                GradientSpan {
+                    start: center - dir,
+                    end: center + dir,
+                    radial: false,
+                }
+            },
+            GradientKind::Radial { center: c, radius } => GradientSpan {
+                start: min + (max - min) * c,
+                end: Vector::new(radius * half.x().max(half.y()), 0f32),
+                radial: true,
+            },
+        }
+    }
+
+    // Premultiplied color at t, interpolating between the surrounding stops
+    pub fn color_at(&self, t: f32) -> [f32; 4] {
+        let first = match self.stops.first() {
+            Some(s) => s,
+            None => return [0f32; 4],
+        };
+
+        if t <= first.0 {
+            return first.1.to_float();
+        }
+
+        for w in self.stops.windows(2) {
+            let ((t0, c0), (t1, c1)) = (w[0], w[1]);
+
+            if t <= t1 {
+                let f = if t1 > t0 { (t - t0) / (t1 - t0) } else { 1f32 };
+                let (a, b) = (c0.to_float(), c1.to_float());
+
+                return [
+                    a[0] + (b[0] - a[0]) * f,
+                    a[1] + (b[1] - a[1]) * f,
+                    a[2] + (b[2] - a[2]) * f,
+                    a[3] + (b[3] - a[3]) * f,
+                ];
+            }
+        }
+
+        self.stops[self.stops.len() - 1].1.to_float()
+    }
+
+    // Bakes the stops into a premultiplied RAMP_SIZE x 1 image, sampled by t
+    pub fn ramp(&self) -> Frame {
+        let data = (0..RAMP_SIZE)
+            .flat_map(|i| {
+                let c = self.color_at((i as f32 + 0.5f32) / RAMP_SIZE as f32);
+
+                c.iter()
+                    .map(|v| (v.max(0f32).min(1f32) * u8::MAX as f32).round() as u8)
+                    .collect::<Vec<u8>>()
+            })
+            .collect();
+
+        Frame::new(RAMP_SIZE, 1, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linear(stops: Vec<(f32, Color)>) -> Result<Gradient> {
+        Gradient::new(GradientKind::Linear { angle: 0f32 }, stops)
+    }
+
+    fn black_to_white() -> Gradient {
+        linear(vec![(1f32, Color::new(255, 255, 255, 255)), (0f32, Color::new(0, 0, 0, 255))]).unwrap()
+    }
+
+    #[test]
+    fn rejects_invalid_stops() {
+        let c = Color::new(0, 0, 0, 255);
+
+        for stops in vec![
+            vec![],
+            vec![(0f32, c)],
+            vec![(0f32, c), (f32::NAN, c)],
+            vec![(-0.1f32, c), (1f32, c)],
+            vec![(0f32, c), (1.5f32, c)],
+        ] {
+            match linear(stops) {
+                Err(CoreError::InvalidGradient(_)) => (),
+                other => panic!("{:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn sorts_stops() {
+        let offsets: Vec<f32> = black_to_white().stops.iter().map(|s| s.0).collect();
+
+        assert_eq!(offsets, vec![0f32, 1f32]);
+    }
+
+    #[test]
+    fn interpolates_colors() {
+        let g = black_to_white();
+
+        assert_eq!(g.color_at(-1f32), [0f32, 0f32, 0f32, 1f32]);
+        assert_eq!(g.color_at(0.25f32), [0.25f32, 0.25f32, 0.25f32, 1f32]);
+        assert_eq!(g.color_at(2f32), [1f32, 1f32, 1f32, 1f32]);
+
+        // Colors are premultiplied before they are interpolated
+        let fade = linear(vec![(0f32, Color::new(255, 0, 0, 255)), (1f32, Color::new(0, 0, 255, 0))]).unwrap();
+        assert_eq!(fade.color_at(0.5f32), [0.5f32, 0f32, 0f32, 0.5f32]);
+
+        // Equal offsets make a hard edge
+        let edge = linear(vec![
+            (0f32, Color::new(0, 0, 0, 255)),
+            (0.5f32, Color::new(0, 0, 0, 255)),
+            (0.5f32, Color::new(255, 255, 255, 255)),
+            (1f32, Color::new(255, 255, 255, 255)),
+        ]).unwrap();
+        assert_eq!(edge.color_at(0.49f32), [0f32, 0f32, 0f32, 1f32]);
+        assert_eq!(edge.color_at(0.51f32), [1f32, 1f32, 1f32, 1f32]);
+    }
+
+    #[test]
+    fn bakes_ramp() {
+        let ramp = black_to_white().ramp();
+
+        assert_eq!((ramp.width, ramp.height), (RAMP_SIZE, 1));
+        assert_eq!(ramp.pixel(0, 0), [0, 0, 0, 255]);
+        assert_eq!(ramp.pixel(RAMP_SIZE / 2, 0), [128, 128, 128, 255]);
+        assert_eq!(ramp.pixel(RAMP_SIZE - 1, 0), [255, 255, 255, 255]);
+    }
+}
@@ -0,0 +1,203 @@
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
+use vulkano::device::{Device, DeviceExtensions, Queue};
+use vulkano::format::Format;
+use vulkano::framebuffer::RenderPassAbstract;
+use vulkano::image::{AttachmentImage, ImageUsage};
+use vulkano::instance::{Instance, InstanceExtensions, PhysicalDevice, PhysicalDeviceType};
+use vulkano::pipeline::viewport::Viewport;
+use vulkano::sync::GpuFuture;
+
+use std::sync::Arc;
+
+use crate::util::*;
+use crate::widget::Widget;
+use crate::decoder::WidgetConfig;
+use crate::frame::Frame;
+use crate::error::{CoreError, Result};
+use crate::pipelines::ShapesPipeline;
+use crate::render::{Backend, VulkanRenderer, VulkanUploader};
+use crate::software::SoftwareRenderer;
+
+/// HeadlessState renders widgets into offscreen images instead of a window Swapchain,
+/// so widgets can be previewed without a display.
+pub enum HeadlessState {
+    Vulkan(VulkanHeadless),
+    Software,
+}
+
+impl HeadlessState {
+    pub fn new(backend: Backend) -> Result<HeadlessState> {
+        HeadlessState::with_msaa(backend, 1, None)
+    }
+
+    /// Same as new, the Vulkan backend rendering with samples per pixel on the device picked
+    /// by selector, or the highest ranked one without.
+    pub fn with_msaa(backend: Backend, samples: u32, selector: Option<&DeviceSelector>) -> Result<HeadlessState> {
+        match backend {
+            Backend::Vulkan => Ok(HeadlessState::Vulkan(VulkanHeadless::new(samples, selector)?)),
+            Backend::Software => Ok(HeadlessState::Software),
+        }
+    }
+
+    /// Same as new, with the device and the MSAA of config.toml, so frames come from the
+    /// same device the windows are drawn with.
+    pub fn from_config(backend: Backend, config: &CoreConfig) -> Result<HeadlessState> {
+        HeadlessState::with_msaa(backend, config.msaa.unwrap_or(1), config.device.as_ref())
+    }
+
+    /// Renders a single frame of the widget described by config and reads it back.
+    pub fn render(&self, config: WidgetConfig) -> Result<Frame> {
+        let mut widget = Widget::new(config)?;
+
+        self.render_widget(&mut widget)
+    }
+
+    pub fn render_widget(&self, widget: &mut Widget) -> Result<Frame> {
+        match self {
+            HeadlessState::Vulkan(v) => v.render_widget(widget),
+            HeadlessState::Software => {
+                let mut renderer = SoftwareRenderer::new(widget.bound.x() as u32, widget.bound.y() as u32);
+
+                widget.draw(&mut renderer)?;
+
+                Ok(renderer.to_frame())
+            },
+        }
+    }
+}
+
+// sRGB like the swapchain picked by surface_format, so frames match what a window shows
+const TARGET_FORMAT: Format = Format::R8G8B8A8Srgb;
+
+/// VulkanHeadless draws with the same ShapesPipeline as the windowed path, into an AttachmentImage.
+pub struct VulkanHeadless {
+    pub instance: Arc<Instance>,
+    pub physical_index: usize,
+
+    pub queue: Arc<Queue>,
+    pub device: Arc<Device>,
+
+    pub render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    pub samples: u32,
+    pub stencil: Format,
+    pub pipelines: ShapesPipeline,
+}
+
+impl VulkanHeadless {
+    /// Samples above 1 render with MSAA, lowered to what the device supports.
+    pub fn new(samples: u32, selector: Option<&DeviceSelector>) -> Result<VulkanHeadless> {
+        let instance = Instance::new(None, &InstanceExtensions::none(), None)?;
+        let physical_index = find_device_index(instance.clone(), selector)?;
+        let physical = PhysicalDevice::from_index(&instance, physical_index).unwrap();
+
+        let queue_family = physical
+        .queue_families()
+        .find(|&q| q.supports_graphics())
+        .unwrap();
+
+        let (device, mut queues) = Device::new(
+            physical,
+            physical.supported_features(),
+            &DeviceExtensions::none(),
+            [(queue_family, 0.5)].iter().cloned(),
+        )?;
+
+        let queue = queues.next().unwrap();
+
+        let samples = sample_count(&physical, samples);
+
+        let stencil = stencil_format(&physical);
+        let render_pass = create_render_pass(device.clone(), TARGET_FORMAT, stencil, samples)?;
+
+        let pipelines = ShapesPipeline::new(device.clone(), render_pass.clone())?;
+
+        Ok(VulkanHeadless {
+            instance,
+            physical_index,
+            queue,
+            device,
+            render_pass,
+            samples,
+            stencil,
+            pipelines,
+        })
+    }
+
+    pub fn render_widget(&self, widget: &mut Widget) -> Result<Frame> {
+        let width = widget.bound.x() as u32;
+        let height = widget.bound.y() as u32;
+
+        let image = AttachmentImage::with_usage(
+            self.device.clone(),
+            [width, height],
+            TARGET_FORMAT,
+            ImageUsage {
+                transfer_source: true,
+                color_attachment: true,
+                ..ImageUsage::none()
+            },
+        )?;
+
+        let framebuffer = create_framebuffer(self.device.clone(), self.render_pass.clone(), image.clone(), self.stencil, self.samples)?;
+
+        let dynamic_state = DynamicState {
+            viewports: Some(vec![Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [width as f32, height as f32],
+                depth_range: 0.0..1.0,
+            }]),
+            ..DynamicState::none()
+        };
+
+        let output = CpuAccessibleBuffer::from_iter(
+            self.device.clone(), BufferUsage::all(), false,
+            (0..width * height * 4).map(|_| 0u8)
+        )?;
+
+        let mut uploader = VulkanUploader::new(
+            self.device.clone(),
+            self.queue.clone(),
+            &self.pipelines,
+            widget.bound,
+        );
+
+        widget.draw(&mut uploader)?;
+
+        let uploads = uploader.finish();
+
+        let mut builder = AutoCommandBufferBuilder::primary_one_time_submit(
+            self.device.clone(),
+            self.queue.family(),
+        )?;
+
+        let mut renderer = VulkanRenderer::new(
+            self.device.clone(),
+            self.queue.clone(),
+            &mut builder,
+            framebuffer,
+            &self.pipelines,
+            &dynamic_state,
+            widget.bound,
+            self.samples,
+        );
+
+        widget.draw(&mut renderer)?;
+
+        builder.copy_image_to_buffer(image.clone(), output.clone())?;
+
+        let command_buffer = builder.build()?;
+
+        uploads
+            .then_execute(self.queue.clone(), command_buffer)?
+            .then_signal_fence_and_flush()?
+            .wait(None)?;
+
+        let data = output.read()?.to_vec();
+
+        let mut frame = Frame::new(width, height, data);
+        frame.unpremultiply();
+
+        Ok(frame)
+    }
+}
@@ -5,12 +5,26 @@ pub mod util;
 pub mod core;
 pub mod decoder;
 pub mod widget;
+pub mod scene;
 pub mod action;
 pub mod tools;
 pub mod error;
 pub mod pipelines;
 pub mod shape;
-//pub mod render;
+pub mod tessellate;
+pub mod path;
+pub mod text;
+pub mod gradient;
+pub mod effects;
+pub mod clip;
+pub mod render;
+pub mod software;
+pub mod batch;
 pub mod buffer;
 pub mod color;
-pub mod vector;
\ No newline at end of file
+pub mod vector;
+pub mod transform;
+pub mod frame;
+pub mod headless;
+pub mod present;
+pub mod snapshot;
\ No newline at end of file
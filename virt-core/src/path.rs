@@ -0,0 +1,441 @@
+use std::f32::consts::PI;
+
+use crate::vector::Vector;
+use crate::tessellate::{self, TOLERANCE};
+use crate::error::{CoreError, Result};
+
+/// Contour is one flattened subpath of an SVG path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contour {
+    pub points: Vec<Vector>,
+    pub closed: bool,
+}
+
+/// Parses SVG path data (the d attribute) and flattens every curve into line segments.
+///
+/// Supports M, L, H, V, C, S, Q, T, A and Z in both absolute and relative form.
+pub fn parse(d: &str) -> Result<Vec<Contour>> {
+    let mut parser = Parser {
+        chars: d.chars().collect(),
+        i: 0,
+    };
+
+    let mut contours = Vec::new();
+    let mut points: Vec<Vector> = Vec::new();
+
+    let mut current = Vector::default();
+    let mut start = Vector::default();
+
+    // Last control point, for the smooth S and T commands
+    let mut last_cubic: Option<Vector> = None;
+    let mut last_quad: Option<Vector> = None;
+
+    let mut command = match parser.command() {
+        Some(c) if c == 'M' || c == 'm' => c,
+        _ => return Err(CoreError::InvalidPathData),
+    };
+
+    loop {
+        let relative = command.is_ascii_lowercase();
+        let base = if relative { current } else { Vector::default() };
+
+        let mut cubic = None;
+        let mut quad = None;
+
+        match command.to_ascii_uppercase() {
+            'M' => {
+                if points.len() > 1 {
+                    contours.push(Contour { points: points.clone(), closed: false });
+                }
+
+                current = base + parser.point()?;
+                start = current;
+                points = vec![current];
+
+                // Extra coordinate pairs after a move are implicit line commands
+                command = if relative { 'l' } else { 'L' };
+            },
+            'L' => {
+                current = base + parser.point()?;
+                points.push(current);
+            },
+            'H' => {
+                let x = parser.number()?;
+                current = Vector::new(if relative { current.x() + x } else { x }, current.y());
+                points.push(current);
+            },
+            'V' => {
+                let y = parser.number()?;
+                current = Vector::new(current.x(), if relative { current.y() + y } else { y });
+                points.push(current);
+            },
+            'C' | 'S' => {
+                let c1 = if command.to_ascii_uppercase() == 'C' {
+                    base + parser.point()?
+                } else {
+                    match last_cubic {
+                        Some(c) => current * 2f32 - c,
+                        None => current,
+                    }
+                };
+                let c2 = base + parser.point()?;
+                let end = base + parser.point()?;
+
+                flatten_cubic(&mut points, current, c1, c2, end);
+
+                cubic = Some(c2);
+                current = end;
+            },
+            'Q' | 'T' => {
+                let c = if command.to_ascii_uppercase() == 'Q' {
+                    base + parser.point()?
+                } else {
+                    match last_quad {
+                        Some(c) => current * 2f32 - c,
+                        None => current,
+                    }
+                };
+                let end = base + parser.point()?;
+
+                flatten_quad(&mut points, current, c, end);
+
+                quad = Some(c);
+                current = end;
+            },
+            'A' => {
+                let radius = Vector::new(parser.number()?.abs(), parser.number()?.abs());
+                let rotation = parser.number()?;
+                let large = parser.flag()?;
+                let sweep = parser.flag()?;
+                let end = base + parser.point()?;
+
+                flatten_arc(&mut points, current, radius, rotation, large, sweep, end);
+
+                current = end;
+            },
+            'Z' => {
+                if points.len() > 1 {
+                    contours.push(Contour { points: points.clone(), closed: true });
+                }
+
+                current = start;
+                points = vec![current];
+            },
+            _ => return Err(CoreError::InvalidPathData),
+        };
+
+        last_cubic = cubic;
+        last_quad = quad;
+
+        if !parser.at_number() {
+            match parser.command() {
+                Some(c) => command = c,
+                None => break,
+            }
+        } else if command.to_ascii_uppercase() == 'Z' {
+            return Err(CoreError::InvalidPathData);
+        }
+    }
+
+    if points.len() > 1 {
+        contours.push(Contour { points, closed: false });
+    }
+
+    Ok(contours)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    i: usize,
+}
+
+impl Parser {
+    fn skip_separators(&mut self) {
+        while self.i < self.chars.len() && (self.chars[self.i].is_whitespace() || self.chars[self.i] == ',') {
+            self.i += 1;
+        }
+    }
+
+    fn at_number(&mut self) -> bool {
+        self.skip_separators();
+
+        match self.chars.get(self.i) {
+            Some(&c) => c.is_ascii_digit() || c == '-' || c == '+' || c == '.',
+            None => false,
+        }
+    }
+
+    fn command(&mut self) -> Option<char> {
+        self.skip_separators();
+
+        match self.chars.get(self.i) {
+            Some(&c) if c.is_ascii_alphabetic() => {
+                self.i += 1;
+                Some(c)
+            },
+            _ => None,
+        }
+    }
+
+    fn number(&mut self) -> Result<f32> {
+        self.skip_separators();
+
+        let begin = self.i;
+        let mut seen_dot = false;
+        let mut seen_exp = false;
+
+        if self.i < self.chars.len() && (self.chars[self.i] == '-' || self.chars[self.i] == '+') {
+            self.i += 1;
+        }
+
+        while self.i < self.chars.len() {
+            let c = self.chars[self.i];
+
+            if c.is_ascii_digit() {
+                self.i += 1;
+            } else if c == '.' && !seen_dot && !seen_exp {
+                seen_dot = true;
+                self.i += 1;
+            } else if (c == 'e' || c == 'E') && !seen_exp && self.i > begin {
+                seen_exp = true;
+                self.i += 1;
+
+                if self.i < self.chars.len() && (self.chars[self.i] == '-' || self.chars[self.i] == '+') {
+                    self.i += 1;
+                }
+            } else {
+                break;
+            }
+        }
+
+        let text: String = self.chars[begin..self.i].iter().collect();
+
+        text.parse::<f32>().map_err(|_| CoreError::InvalidPathData)
+    }
+
+    fn point(&mut self) -> Result<Vector> {
+        Ok(Vector::new(self.number()?, self.number()?))
+    }
+
+    // Arc flags are a single 0 or 1 and may be written without a separator, "a1 1 0 01 5 5"
+    fn flag(&mut self) -> Result<bool> {
+        self.skip_separators();
+
+        match self.chars.get(self.i) {
+            Some('0') => {
+                self.i += 1;
+                Ok(false)
+            },
+            Some('1') => {
+                self.i += 1;
+                Ok(true)
+            },
+            _ => Err(CoreError::InvalidPathData),
+        }
+    }
+}
+
+fn subdivisions(deviation: f32) -> usize {
+    (deviation.sqrt().ceil() as usize).max(1).min(256)
+}
+
+fn flatten_quad(points: &mut Vec<Vector>, p0: Vector, p1: Vector, p2: Vector) {
+    // Wang's formula for the segment count that keeps the error below TOLERANCE
+    let dd = (p0 - p1 * 2f32 + p2).hypot();
+    let n = subdivisions(dd / (4f32 * TOLERANCE));
+
+    for i in 1..=n {
+        let t = i as f32 / n as f32;
+        let mt = 1f32 - t;
+
+        points.push(p0 * (mt * mt) + p1 * (2f32 * mt * t) + p2 * (t * t));
+    }
+}
+
+fn flatten_cubic(points: &mut Vec<Vector>, p0: Vector, p1: Vector, p2: Vector, p3: Vector) {
+    let dd = (p0 - p1 * 2f32 + p2).hypot().max((p1 - p2 * 2f32 + p3).hypot());
+    let n = subdivisions(3f32 * dd / (4f32 * TOLERANCE));
+
+    for i in 1..=n {
+        let t = i as f32 / n as f32;
+        let mt = 1f32 - t;
+
+        points.push(
+            p0 * (mt * mt * mt)
+            + p1 * (3f32 * mt * mt * t)
+            + p2 * (3f32 * mt * t * t)
+            + p3 * (t * t * t)
+        );
+    }
+}
+
+fn angle_between(u: Vector, v: Vector) -> f32 {
+    u.cross(v).atan2(u.dot(v))
+}
+
+// Converts the SVG endpoint arc parameters to a center and angles, as described in
+// the SVG implementation notes, and flattens it.
+fn flatten_arc(
+    points: &mut Vec<Vector>,
+    p0: Vector,
+    radius: Vector,
+    rotation: f32,
+    large: bool,
+    sweep: bool,
+    p1: Vector,
+) {
+    if p0 == p1 {
+        return;
+    }
+
+    if radius.x() == 0f32 || radius.y() == 0f32 {
+        points.push(p1);
+        return;
+    }
+
+    let (sin, cos) = rotation.to_radians().sin_cos();
+
+    let half = (p0 - p1) / 2f32;
+    let x1 = cos * half.x() + sin * half.y();
+    let y1 = -sin * half.x() + cos * half.y();
+
+    // Scale up radii that are too small to reach the end point
+    let (mut rx, mut ry) = (radius.x(), radius.y());
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1f32 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+
+    let num = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+    let den = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let sign = if large == sweep { -1f32 } else { 1f32 };
+    let coef = sign * (num / den).max(0f32).sqrt();
+
+    let cx1 = coef * rx * y1 / ry;
+    let cy1 = coef * -ry * x1 / rx;
+
+    let mid = (p0 + p1) / 2f32;
+    let center = Vector::new(cos * cx1 - sin * cy1 + mid.x(), sin * cx1 + cos * cy1 + mid.y());
+
+    let u = Vector::new((x1 - cx1) / rx, (y1 - cy1) / ry);
+    let v = Vector::new((-x1 - cx1) / rx, (-y1 - cy1) / ry);
+
+    let start = angle_between(Vector::new(1f32, 0f32), u);
+    let mut delta = angle_between(u, v);
+
+    if !sweep && delta > 0f32 {
+        delta -= 2f32 * PI;
+    } else if sweep && delta < 0f32 {
+        delta += 2f32 * PI;
+    }
+
+    let n = tessellate::arc_segments(rx.max(ry), delta);
+
+    for i in 1..=n {
+        let (s, c) = (start + delta * i as f32 / n as f32).sin_cos();
+
+        points.push(Vector::new(
+            cos * rx * c - sin * ry * s + center.x(),
+            sin * rx * c + cos * ry * s + center.y(),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(values: &[f32]) -> Vec<Vector> {
+        values.chunks(2).map(|c| Vector::new(c[0], c[1])).collect()
+    }
+
+    fn single(d: &str) -> Contour {
+        let mut contours = parse(d).unwrap();
+
+        assert_eq!(contours.len(), 1, "{}", d);
+        contours.remove(0)
+    }
+
+    fn near(a: Vector, b: Vector) -> bool {
+        (a - b).hypot() < 1e-3
+    }
+
+    #[test]
+    fn parses_lines() {
+        assert_eq!(single("M 10 20 L 30 40 Z"), Contour { points: points(&[10., 20., 30., 40.]), closed: true });
+        assert_eq!(single("M 0 0 H 10 V 10 h -5 v -5").points, points(&[0., 0., 10., 0., 10., 10., 5., 10., 5., 5.]));
+        assert_eq!(single("m 10 10 l 5 0 l 0 5 z"), Contour { points: points(&[10., 10., 15., 10., 15., 15.]), closed: true });
+    }
+
+    #[test]
+    fn repeats_implicit_commands() {
+        // Pairs after a move are lines, pairs after any command repeat it
+        assert_eq!(single("M 0 0 10 0 10 10").points, points(&[0., 0., 10., 0., 10., 10.]));
+        assert_eq!(single("m 5 5 10 0 0 10").points, points(&[5., 5., 15., 5., 15., 15.]));
+        assert_eq!(single("M 0 0 L 1 1 2 2").points, points(&[0., 0., 1., 1., 2., 2.]));
+        assert_eq!(single("m 0 0 l 1 0 1 0").points, points(&[0., 0., 1., 0., 2., 0.]));
+        assert_eq!(single("M-1-2L.5.5 1e1,0").points, points(&[-1., -2., 0.5, 0.5, 10., 0.]));
+    }
+
+    #[test]
+    fn flattens_curves() {
+        let cubic = single("M 0 0 C 0 10 10 10 10 0").points;
+        assert!(near(*cubic.last().unwrap(), Vector::new(10., 0.)));
+        assert!(cubic.len() > 4);
+        // The peak of the curve is at 7.5, the segments stay within TOLERANCE of it
+        let peak = cubic.iter().fold(0f32, |m, p| m.max(p.y()));
+        assert!(peak <= 7.5 && peak >= 7.5 - TOLERANCE, "{}", peak);
+
+        let relative = single("M 5 5 c 0 10 10 10 10 0 s 10 -10 10 0").points;
+        assert!(relative.iter().any(|&p| near(p, Vector::new(15., 5.))));
+        assert!(near(*relative.last().unwrap(), Vector::new(25., 5.)));
+        assert!(relative.iter().any(|p| p.y() < 0f32));
+
+        let quad = single("M 0 0 Q 5 10 10 0 T 20 0").points;
+        // T mirrors the control point, so the second half bulges the other way as far
+        let (low, high) = quad.iter().fold((0f32, 0f32), |(l, h), p| (l.min(p.y()), h.max(p.y())));
+        assert!(high <= 5. && high >= 5. - TOLERANCE, "{}", high);
+        assert!(low >= -5. && low <= -5. + TOLERANCE, "{}", low);
+        assert!(near(*quad.last().unwrap(), Vector::new(20., 0.)));
+
+        let relative = single("M 10 10 q 5 10 10 0").points;
+        assert!(near(*relative.last().unwrap(), Vector::new(20., 10.)));
+    }
+
+    #[test]
+    fn flattens_arcs() {
+        for d in ["M 0 0 A 10 10 0 0 1 20 0", "M 0 0 a 10 10 0 0 1 20 0", "M0 0a10 10 0 0120 0"].iter() {
+            let arc = single(d).points;
+
+            assert!(near(*arc.last().unwrap(), Vector::new(20., 0.)), "{}", d);
+            assert!(arc.iter().all(|&p| ((p - Vector::new(10., 0.)).hypot() - 10.).abs() < 1e-3), "{}", d);
+        }
+
+        // The sweep flag picks the side the half circle bulges to
+        let (a, b) = (single("M 0 0 A 10 10 0 0 1 20 0").points, single("M 0 0 A 10 10 0 0 0 20 0").points);
+        assert_eq!(a[a.len() / 2].y().signum(), -b[b.len() / 2].y().signum());
+
+        // Radii too small to reach the end are scaled up
+        let small = single("M 0 0 A 1 1 0 0 1 20 0").points;
+        assert!(near(*small.last().unwrap(), Vector::new(20., 0.)));
+    }
+
+    #[test]
+    fn parses_subpaths() {
+        let contours = parse("M 0 0 L 10 0 L 10 10 Z M 20 20 l 5 0 l 0 5 z").unwrap();
+
+        assert_eq!(contours.len(), 2);
+        assert_eq!(contours[1].points, points(&[20., 20., 25., 20., 25., 25.]));
+
+        // A move alone draws nothing
+        assert!(parse("M 5 5").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_data() {
+        for d in ["", "L 10 10", "M 10", "M 0 0 Z 5", "M 0 0 X 1 1", "M 0 0 A 1 1 0 2 0 5 5", "M 0 0 L 1 x"].iter() {
+            assert!(parse(d).is_err(), "{}", d);
+        }
+    }
+}
@@ -1,14 +1,26 @@
 use vulkano::device::Device;
 use vulkano::framebuffer::{RenderPassAbstract, Subpass};
 use vulkano::pipeline::{GraphicsPipelineAbstract, GraphicsPipeline};
+use vulkano::pipeline::blend::{AttachmentBlend, BlendFactor, BlendOp};
+use vulkano::pipeline::depth_stencil::{Compare, DepthBounds, DepthStencil, Stencil, StencilOp};
 
 use std::sync::Arc;
 
 use crate::vector::Vector;
+use crate::render::BlendMode;
+use crate::buffer::{TexturedVertex, ColoredVertex};
 use crate::error::Result;
 
 pub struct ShapesPipeline {
-    pub default_fill: Arc<dyn GraphicsPipelineAbstract + Send + Sync>
+    pub default_fill: Blended,
+    pub rounded_rect: Blended,
+    pub textured: Blended,
+    pub gradient: Blended,
+    pub batch: Blended,
+
+    // Raise and lower the stencil inside a clip without touching the colors
+    pub clip_push: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    pub clip_pop: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
 }
 
 pub mod vs {
@@ -20,11 +32,13 @@ pub mod vs {
         layout(location = 0) in vec2 position;
         
         layout(push_constant) uniform PushConstantData {
+            mat2 linear;
             vec2 resolution;
             float r;
             float g;
             float b;
             float a;
+            vec2 translation;
         } pc;
         
         layout(location = 0) out vec4 v_color;
@@ -32,7 +46,7 @@ pub mod vs {
         void main() {
             v_color = vec4(pc.r, pc.g, pc.b, pc.a);
         
-            vec2 norm = (position + 0.5) / (pc.resolution / 2.0) - vec2(1.0, 1.0);
+            vec2 norm = (pc.linear * position + pc.translation + 0.5) / (pc.resolution / 2.0) - vec2(1.0, 1.0);
         
             gl_Position = vec4(norm, 0.0, 1.0);
         }",
@@ -55,19 +69,423 @@ pub mod fs {
     ",}
 }
 
+// Rounded rectangles are drawn as a quad with the edges computed from a signed distance
+// field in the fragment shader, which gives anti-aliased corners.
+pub mod rounded_rect_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+        #version 450
+
+        layout(location = 0) in vec2 position;
+
+        layout(push_constant) uniform PushConstantData {
+            vec4 radius;
+            vec4 color;
+            mat2 linear;
+            vec2 resolution;
+            vec2 origin;
+            vec2 size;
+            vec2 translation;
+        } pc;
+
+        layout(location = 0) out vec4 v_color;
+        layout(location = 1) out vec2 v_local;
+        layout(location = 2) flat out vec2 v_half;
+        layout(location = 3) flat out vec4 v_radius;
+
+        void main() {
+            v_color = pc.color;
+            v_half = pc.size / 2.0;
+            v_local = position + 0.5 - pc.origin - v_half;
+            v_radius = pc.radius;
+
+            vec2 norm = (pc.linear * position + pc.translation + 0.5) / (pc.resolution / 2.0) - vec2(1.0, 1.0);
+
+            gl_Position = vec4(norm, 0.0, 1.0);
+        }",
+    }
+}
+
+pub mod rounded_rect_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
+        #version 450
+
+        layout(location = 0) in vec4 v_color;
+        layout(location = 1) in vec2 v_local;
+        layout(location = 2) flat in vec2 v_half;
+        layout(location = 3) flat in vec4 v_radius;
+
+        layout(location = 0) out vec4 f_color;
+
+        // radius is top left, top right, bottom right, bottom left
+        float rounded_rect(vec2 p, vec2 half_size, vec4 radius) {
+            float r = p.x > 0.0
+                ? (p.y > 0.0 ? radius.z : radius.y)
+                : (p.y > 0.0 ? radius.w : radius.x);
+
+            vec2 q = abs(p) - half_size + r;
+
+            return min(max(q.x, q.y), 0.0) + length(max(q, 0.0)) - r;
+        }
+
+        void main() {
+            float d = rounded_rect(v_local, v_half, v_radius);
+
+            f_color = v_color * clamp(0.5 - d, 0.0, 1.0);
+        }
+    ",}
+}
+
+// Textured geometry samples a premultiplied RGBA texture and tints it with the color,
+// used for glyph atlases as well as images.
+pub mod textured_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+        #version 450
+
+        layout(location = 0) in vec2 position;
+        layout(location = 1) in vec2 uv;
+
+        layout(push_constant) uniform PushConstantData {
+            vec4 color;
+            mat2 linear;
+            vec2 resolution;
+            vec2 translation;
+        } pc;
+
+        layout(location = 0) out vec4 v_color;
+        layout(location = 1) out vec2 v_uv;
+
+        void main() {
+            v_color = pc.color;
+            v_uv = uv;
+
+            vec2 norm = (pc.linear * position + pc.translation + 0.5) / (pc.resolution / 2.0) - vec2(1.0, 1.0);
+
+            gl_Position = vec4(norm, 0.0, 1.0);
+        }",
+    }
+}
+
+pub mod textured_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
+        #version 450
+
+        layout(location = 0) in vec4 v_color;
+        layout(location = 1) in vec2 v_uv;
+
+        layout(set = 0, binding = 0) uniform sampler2D tex;
+
+        layout(location = 0) out vec4 f_color;
+
+        // Textures hold premultiplied sRGB, drawing happens in premultiplied linear light
+        vec4 to_linear(vec4 c) {
+            if (c.a <= 0.0) {
+                return vec4(0.0);
+            }
+
+            vec3 s = c.rgb / c.a;
+            vec3 l = mix(s / 12.92, pow((s + 0.055) / 1.055, vec3(2.4)), step(0.04045, s));
+
+            return vec4(l * c.a, c.a);
+        }
+
+        void main() {
+            f_color = to_linear(texture(tex, v_uv)) * v_color;
+        }
+    ",}
+}
+
+// Gradients compute t per fragment, which keeps radial gradients round on coarse
+// geometry, and look the color up in a ramp texture baked from the stops.
+pub mod gradient_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+        #version 450
+
+        layout(location = 0) in vec2 position;
+
+        layout(push_constant) uniform PushConstantData {
+            vec2 start;
+            vec2 end;
+            mat2 linear;
+            vec2 resolution;
+            vec2 translation;
+            uint radial;
+        } pc;
+
+        layout(location = 0) out vec2 v_position;
+        layout(location = 1) flat out vec2 v_start;
+        layout(location = 2) flat out vec2 v_end;
+        layout(location = 3) flat out uint v_radial;
+
+        void main() {
+            v_position = position;
+            v_start = pc.start;
+            v_end = pc.end;
+            v_radial = pc.radial;
+
+            vec2 norm = (pc.linear * position + pc.translation + 0.5) / (pc.resolution / 2.0) - vec2(1.0, 1.0);
+
+            gl_Position = vec4(norm, 0.0, 1.0);
+        }",
+    }
+}
+
+pub mod gradient_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
+        #version 450
+
+        layout(location = 0) in vec2 v_position;
+        layout(location = 1) flat in vec2 v_start;
+        layout(location = 2) flat in vec2 v_end;
+        layout(location = 3) flat in uint v_radial;
+
+        layout(set = 0, binding = 0) uniform sampler2D ramp;
+
+        layout(location = 0) out vec4 f_color;
+
+        // Textures hold premultiplied sRGB, drawing happens in premultiplied linear light
+        vec4 to_linear(vec4 c) {
+            if (c.a <= 0.0) {
+                return vec4(0.0);
+            }
+
+            vec3 s = c.rgb / c.a;
+            vec3 l = mix(s / 12.92, pow((s + 0.055) / 1.055, vec3(2.4)), step(0.04045, s));
+
+            return vec4(l * c.a, c.a);
+        }
+
+        void main() {
+            float t;
+
+            if (v_radial != 0) {
+                t = length(v_position - v_start) / v_end.x;
+            } else {
+                vec2 d = v_end - v_start;
+                t = dot(v_position - v_start, d) / dot(d, d);
+            }
+
+            f_color = to_linear(texture(ramp, vec2(clamp(t, 0.0, 1.0), 0.5)));
+        }
+    ",}
+}
+
+// Batches carry the color per vertex instead of in the push constants, so every
+// solid fill of a widget can be drawn with one call.
+pub mod batch_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
+        #version 450
+
+        layout(location = 0) in vec2 position;
+        layout(location = 1) in vec4 color;
+
+        layout(push_constant) uniform PushConstantData {
+            mat2 linear;
+            vec2 resolution;
+            vec2 translation;
+        } pc;
+
+        layout(location = 0) out vec4 v_color;
+
+        void main() {
+            v_color = color;
+
+            vec2 norm = (pc.linear * position + pc.translation + 0.5) / (pc.resolution / 2.0) - vec2(1.0, 1.0);
+
+            gl_Position = vec4(norm, 0.0, 1.0);
+        }",
+    }
+}
+
+/// Blended holds a pipeline for every BlendMode, otherwise drawing the same.
+pub struct Blended {
+    pipelines: Vec<Arc<dyn GraphicsPipelineAbstract + Send + Sync>>,
+}
+
+impl Blended {
+    // Builds the pipeline once per mode with its blend state
+    fn new<F>(build: F) -> Result<Blended>
+        where F: Fn(AttachmentBlend) -> Result<Arc<dyn GraphicsPipelineAbstract + Send + Sync>>
+    {
+        Ok(Blended {
+            pipelines: BlendMode::ALL.iter().map(|&m| build(blending(m))).collect::<Result<_>>()?,
+        })
+    }
+
+    pub fn get(&self, mode: BlendMode) -> Arc<dyn GraphicsPipelineAbstract + Send + Sync> {
+        self.pipelines[mode as usize].clone()
+    }
+}
+
+// Blend state for premultiplied linear colors, matching BlendMode::apply. The sRGB
+// attachments decode what is drawn over and encode the result.
+pub fn blending(mode: BlendMode) -> AttachmentBlend {
+    let (color_source, color_destination) = match mode {
+        BlendMode::Normal => (BlendFactor::One, BlendFactor::OneMinusSrcAlpha),
+        BlendMode::Additive => (BlendFactor::One, BlendFactor::One),
+        BlendMode::Multiply => (BlendFactor::DstColor, BlendFactor::OneMinusSrcAlpha),
+        BlendMode::Screen => (BlendFactor::One, BlendFactor::OneMinusSrcColor),
+    };
+
+    AttachmentBlend {
+        enabled: true,
+        color_op: BlendOp::Add,
+        color_source,
+        color_destination,
+        alpha_op: BlendOp::Add,
+        alpha_source: BlendFactor::One,
+        alpha_destination: BlendFactor::OneMinusSrcAlpha,
+        mask_red: true,
+        mask_green: true,
+        mask_blue: true,
+        mask_alpha: true,
+    }
+}
+
+// Blending that leaves the color attachment as it is, for draws that only write the stencil
+pub fn no_color_writes() -> AttachmentBlend {
+    AttachmentBlend {
+        mask_red: false,
+        mask_green: false,
+        mask_blue: false,
+        mask_alpha: false,
+        ..AttachmentBlend::pass_through()
+    }
+}
+
+// Every pipeline only draws where the stencil equals the reference, the number of clips
+// the draw is inside. op is applied to the stencil wherever the pipeline draws.
+pub fn clip_stencil(op: StencilOp) -> DepthStencil {
+    let stencil = Stencil {
+        compare: Compare::Equal,
+        pass_op: op,
+        fail_op: StencilOp::Keep,
+        depth_fail_op: StencilOp::Keep,
+        compare_mask: Some(0xff),
+        write_mask: Some(0xff),
+        // Set per draw through the DynamicState
+        reference: None,
+    };
+
+    DepthStencil {
+        depth_write: false,
+        depth_compare: Compare::Always,
+        depth_bounds_test: DepthBounds::Disabled,
+        stencil_front: stencil.clone(),
+        stencil_back: stencil,
+    }
+}
+
 impl ShapesPipeline {
     pub fn new(device: Arc<Device>, render_pass: Arc<dyn RenderPassAbstract + Send + Sync>) -> Result<ShapesPipeline> {
         Ok(ShapesPipeline {
-            default_fill: Arc::new(
+            default_fill: Blended::new(|blend| {
+                Ok(Arc::new(
+                    GraphicsPipeline::start()
+                        .vertex_input_single_buffer::<Vector>()
+                        .vertex_shader(vs::Shader::load(device.clone()).unwrap().main_entry_point(), ())
+                        .viewports_scissors_dynamic(1)
+                        .triangle_list()
+                        .fragment_shader(fs::Shader::load(device.clone()).unwrap().main_entry_point(), ())
+                        .blend_collective(blend)
+                        .depth_stencil(clip_stencil(StencilOp::Keep))
+                        .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+                        .build(device.clone())?,
+                ))
+            })?,
+            rounded_rect: Blended::new(|blend| {
+                Ok(Arc::new(
+                    GraphicsPipeline::start()
+                        .vertex_input_single_buffer::<Vector>()
+                        .vertex_shader(rounded_rect_vs::Shader::load(device.clone()).unwrap().main_entry_point(), ())
+                        .viewports_scissors_dynamic(1)
+                        .triangle_list()
+                        .fragment_shader(rounded_rect_fs::Shader::load(device.clone()).unwrap().main_entry_point(), ())
+                        .blend_collective(blend)
+                        .depth_stencil(clip_stencil(StencilOp::Keep))
+                        .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+                        .build(device.clone())?,
+                ))
+            })?,
+            textured: Blended::new(|blend| {
+                Ok(Arc::new(
+                    GraphicsPipeline::start()
+                        .vertex_input_single_buffer::<TexturedVertex>()
+                        .vertex_shader(textured_vs::Shader::load(device.clone()).unwrap().main_entry_point(), ())
+                        .viewports_scissors_dynamic(1)
+                        .triangle_list()
+                        .fragment_shader(textured_fs::Shader::load(device.clone()).unwrap().main_entry_point(), ())
+                        .blend_collective(blend)
+                        .depth_stencil(clip_stencil(StencilOp::Keep))
+                        .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+                        .build(device.clone())?,
+                ))
+            })?,
+            gradient: Blended::new(|blend| {
+                Ok(Arc::new(
+                    GraphicsPipeline::start()
+                        .vertex_input_single_buffer::<Vector>()
+                        .vertex_shader(gradient_vs::Shader::load(device.clone()).unwrap().main_entry_point(), ())
+                        .viewports_scissors_dynamic(1)
+                        .triangle_list()
+                        .fragment_shader(gradient_fs::Shader::load(device.clone()).unwrap().main_entry_point(), ())
+                        .blend_collective(blend)
+                        .depth_stencil(clip_stencil(StencilOp::Keep))
+                        .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+                        .build(device.clone())?,
+                ))
+            })?,
+            batch: Blended::new(|blend| {
+                Ok(Arc::new(
+                    GraphicsPipeline::start()
+                        .vertex_input_single_buffer::<ColoredVertex>()
+                        .vertex_shader(batch_vs::Shader::load(device.clone()).unwrap().main_entry_point(), ())
+                        .viewports_scissors_dynamic(1)
+                        .triangle_list()
+                        .fragment_shader(fs::Shader::load(device.clone()).unwrap().main_entry_point(), ())
+                        .blend_collective(blend)
+                        .depth_stencil(clip_stencil(StencilOp::Keep))
+                        .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+                        .build(device.clone())?,
+                ))
+            })?,
+            clip_push: Arc::new(
+                GraphicsPipeline::start()
+                    .vertex_input_single_buffer::<Vector>()
+                    .vertex_shader(vs::Shader::load(device.clone()).unwrap().main_entry_point(), ())
+                    .viewports_scissors_dynamic(1)
+                    .triangle_list()
+                    .fragment_shader(fs::Shader::load(device.clone()).unwrap().main_entry_point(), ())
+                    .blend_collective(no_color_writes())
+                    .depth_stencil(clip_stencil(StencilOp::IncrementAndClamp))
+                    .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+                    .build(device.clone())?,
+            ),
+            clip_pop: Arc::new(
                 GraphicsPipeline::start()
                     .vertex_input_single_buffer::<Vector>()
                     .vertex_shader(vs::Shader::load(device.clone()).unwrap().main_entry_point(), ())
-                    .viewports_dynamic_scissors_irrelevant(1)
+                    .viewports_scissors_dynamic(1)
                     .triangle_list()
                     .fragment_shader(fs::Shader::load(device.clone()).unwrap().main_entry_point(), ())
+                    .blend_collective(no_color_writes())
+                    .depth_stencil(clip_stencil(StencilOp::DecrementAndClamp))
                     .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
                     .build(device.clone())?,
-            )
+            ),
         })
     }
 }
\ No newline at end of file
@@ -0,0 +1,90 @@
+use winit::window::Window;
+
+use crate::frame::Frame;
+use crate::error::{CoreError, Result};
+
+// Stretches frame over width by height with the nearest pixel, for windows on a scaled display
+fn fit(frame: &Frame, width: u32, height: u32) -> Vec<u8> {
+    let mut data = Vec::with_capacity((width * height * 4) as usize);
+
+    for y in 0..height {
+        for x in 0..width {
+            let sx = (x as u64 * frame.width as u64 / width.max(1) as u64) as u32;
+            let sy = (y as u64 * frame.height as u64 / height.max(1) as u64) as u32;
+
+            data.extend_from_slice(&frame.pixel(sx, sy));
+        }
+    }
+
+    data
+}
+
+/// Copies a frame drawn by the software backend into the window, stretched over all of it.
+/// The frame has to hold premultiplied pixels like a swapchain image does, see
+/// SoftwareRenderer::to_premultiplied_frame, so the compositor blends the transparent parts
+/// of the window the same way for both backends.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn present(window: &Window, frame: &Frame) -> Result<()> {
+    use std::os::raw::{c_char, c_void};
+    use std::ptr;
+    use winit::platform::unix::WindowExtUnix;
+    use winit::platform::unix::x11::ffi;
+
+    let (xconn, xwindow) = match (window.xlib_xconnection(), window.xlib_window()) {
+        (Some(c), Some(w)) => (c, w),
+        _ => return Err(CoreError::PresentUnsupported),
+    };
+
+    let (width, height): (u32, u32) = window.inner_size().into();
+
+    if width == 0 || height == 0 || frame.width == 0 || frame.height == 0 {
+        return Ok(());
+    }
+
+    // 32 bit pixels, blue in the lowest byte like on every little endian X server
+    let mut data: Vec<u8> = fit(frame, width, height)
+        .chunks(4)
+        .flat_map(|px| vec![px[2], px[1], px[0], px[3]])
+        .collect();
+
+    let xlib = &xconn.xlib;
+
+    unsafe {
+        let mut attributes: ffi::XWindowAttributes = std::mem::zeroed();
+        (xlib.XGetWindowAttributes)(xconn.display, xwindow, &mut attributes);
+
+        let image = (xlib.XCreateImage)(
+            xconn.display,
+            attributes.visual,
+            attributes.depth as u32,
+            ffi::ZPixmap,
+            0,
+            data.as_mut_ptr() as *mut c_char,
+            width,
+            height,
+            32,
+            0,
+        );
+
+        if image.is_null() {
+            return Err(CoreError::PresentUnsupported);
+        }
+
+        let gc = (xlib.XCreateGC)(xconn.display, xwindow, 0, ptr::null_mut());
+        (xlib.XPutImage)(xconn.display, xwindow, gc, image, 0, 0, 0, 0, width, height);
+        (xlib.XFreeGC)(xconn.display, gc);
+
+        // The pixels belong to data, only the image itself is freed
+        (*image).data = ptr::null_mut();
+        (xlib.XFree)(image as *mut c_void);
+
+        (xlib.XFlush)(xconn.display);
+    }
+
+    Ok(())
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+pub fn present(_window: &Window, _frame: &Frame) -> Result<()> {
+    Err(CoreError::PresentUnsupported)
+}
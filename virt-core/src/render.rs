@@ -1,56 +1,821 @@
-// use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
-// use vulkano::buffer::{CpuAccessibleBuffer, BufferUsage};
-// use vulkano::device::Device;
-
-// use cgmath::Vector2;
-
-// use std::sync::Arc;
-// use std::ops::Deref;
-
-// use crate::pipelines::{ShapesPipeline, vs::ty::PushConstantData};
-// use crate::shapes::Shape;
-// use crate::error::{Result, CoreError};
-
-// pub struct Renderer {
-//     builder: AutoCommandBufferBuilder,
-//     piplelines: ShapesPipeline,
-//     resolution: Vector2<f32>,
-// }
-
-// impl Renderer {
-//     pub fn draw(&self, device: Arc<Device>, shape: &dyn Shape, dynamic_state: &DynamicState) -> Result<()> {
-//         let (vertexes, indexes) = shape.vertexes();
-
-//         let vertex_buffer = CpuAccessibleBuffer::from_iter(
-//             device.clone(), BufferUsage::all(), false, 
-//             vertexes.iter().cloned()
-//         ).unwrap();
-
-//         match indexes {
-//             Some(i) => {
-//                 let index_buffer = CpuAccessibleBuffer::from_iter(
-//                     device.clone(), BufferUsage::all(), false, 
-//                     i.iter().clone()
-//                 ).unwrap();
-
-//                 self.builder.draw_indexed(
-//                     self.piplelines.default_fill, 
-//                     dynamic_state, 
-//                     vec![vertex_buffer.clone()], 
-//                     index_buffer.clone(), 
-//                     (),
-//                     PushConstantData {
-//                         resolution: [self.resolution.x, self.resolution.y]
-//                     }
-//                 );
-
-//                 return Ok(())
-//             },
-//             None => {
-//                 return Ok(())
-//             }
-//         }
-
-//         //Err(CoreError::Unimplemented)
-//     }
-// }
\ No newline at end of file
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState, DynamicStencilValue, StencilFaceFlags};
+use vulkano::buffer::{CpuAccessibleBuffer, BufferUsage};
+use vulkano::framebuffer::FramebufferAbstract;
+use vulkano::pipeline::GraphicsPipelineAbstract;
+use vulkano::pipeline::viewport::Scissor;
+use vulkano::device::{Device, Queue};
+use vulkano::format::{ClearValue, Format};
+use vulkano::image::{Dimensions, ImmutableImage};
+use vulkano::sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode};
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::sync::{self, GpuFuture};
+
+use serde_derive::Deserialize;
+
+use std::sync::Arc;
+
+use crate::pipelines::{ShapesPipeline, vs, rounded_rect_vs, textured_vs, gradient_vs, batch_vs};
+use crate::buffer::{Buffer, BatchBuffer, ColoredVertex, TexturedBuffer, TexturedVertex};
+use crate::frame::Frame;
+use crate::gradient::{Gradient, GradientSpan};
+use crate::vector::Vector;
+use crate::tessellate;
+use crate::transform::Transform;
+use crate::color::Color;
+use crate::error::Result;
+
+/// Backend selects which Renderer implementation draws the widgets.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+pub enum Backend {
+    Vulkan,
+    Software,
+}
+
+/// BlendMode is how a draw combines with what is already drawn, all of them on premultiplied colors.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BlendMode {
+    // The source over the destination
+    Normal,
+    // Source and destination added up, lightens
+    Additive,
+    // Source times destination, darkens. Assumes an opaque destination like the window background.
+    Multiply,
+    // Inverse of the product of the inverses, lightens
+    Screen,
+}
+
+impl BlendMode {
+    pub const ALL: [BlendMode; 4] = [BlendMode::Normal, BlendMode::Additive, BlendMode::Multiply, BlendMode::Screen];
+
+    /// Blends the premultiplied color src onto dst the same way the blend state of the
+    /// pipeline for the mode does.
+    pub fn apply(&self, src: [f32; 4], dst: [f32; 4]) -> [f32; 4] {
+        let mut out = [0f32; 4];
+
+        for (i, o) in out[..3].iter_mut().enumerate() {
+            *o = match self {
+                BlendMode::Normal => src[i] + dst[i] * (1f32 - src[3]),
+                BlendMode::Additive => (src[i] + dst[i]).min(1f32),
+                BlendMode::Multiply => src[i] * dst[i] + dst[i] * (1f32 - src[3]),
+                BlendMode::Screen => src[i] + dst[i] * (1f32 - src[i]),
+            };
+        }
+
+        // Coverage always combines like Normal
+        out[3] = src[3] + dst[3] * (1f32 - src[3]);
+
+        out
+    }
+}
+
+/// Renderer is the target Shape::draw records into.
+///
+/// All geometry is given in widget space, with 0,0 being the top left of the widget
+/// and resolution() being the bottom right. Colors are sRGB, renderers blend them in
+/// linear light and write sRGB again, so a color looks the same whatever draws it.
+pub trait Renderer {
+    fn resolution(&self) -> Vector;
+
+    // Starts a new frame cleared to the given color
+    fn begin(&mut self, clear: Color) -> Result<()>;
+
+    // Applies transform to everything drawn after it, the world transform of the scene node
+    // being drawn. Cached geometry stays as it is, only where it lands changes.
+    fn transform(&mut self, transform: Transform);
+
+    // The norm of the current transform, so curves can be tessellated finely enough
+    // for the size they end up at on screen
+    fn scale(&self) -> f32;
+
+    // Sets how everything drawn after it is blended, BlendMode::Normal at the start of a frame
+    fn blend(&mut self, mode: BlendMode);
+
+    // Fills an indexed triangle list with a single color.
+    // Backends that upload geometry may cache it in buffer between frames.
+    fn fill(&mut self,
+        buffer: &mut Option<Buffer>,
+        vertices: &[Vector],
+        indices: &[u32],
+        color: Color,
+    ) -> Result<()>;
+
+    // Fills a rectangle with rounded corners, anti-aliasing its edges.
+    // radius is top left, top right, bottom right, bottom left.
+    fn rounded_rect(&mut self,
+        buffer: &mut Option<Buffer>,
+        position: Vector,
+        size: Vector,
+        radius: [f32; 4],
+        color: Color,
+    ) -> Result<()>;
+
+    // Draws an indexed triangle list sampling image, a premultiplied RGBA texture that
+    // repeats outside 0..1 when repeat is set and clamps to its edge otherwise, and
+    // multiplies every texel with color.
+    fn textured(&mut self,
+        buffer: &mut Option<TexturedBuffer>,
+        vertices: &[TexturedVertex],
+        indices: &[u32],
+        image: &Frame,
+        repeat: bool,
+        color: Color,
+    ) -> Result<()>;
+
+    // Fills an indexed triangle list with a gradient placed by span.
+    // The ramp texture is cached in buffer along with the geometry.
+    fn gradient(&mut self,
+        buffer: &mut Option<TexturedBuffer<Vector>>,
+        vertices: &[Vector],
+        indices: &[u32],
+        gradient: &Gradient,
+        span: GradientSpan,
+    ) -> Result<()>;
+
+    // Fills an indexed triangle list where every vertex has its own color,
+    // used to draw many solid shapes at once.
+    fn batch(&mut self,
+        buffer: &mut Option<BatchBuffer>,
+        vertices: &[ColoredVertex],
+        indices: &[u32],
+    ) -> Result<()>;
+
+    // Restricts everything drawn after it to the triangles given, placed by the current
+    // transform, and to the clips pushed before. Clips are not anti-aliased.
+    fn push_clip(&mut self,
+        buffer: &mut Option<Buffer>,
+        vertices: &[Vector],
+        indices: &[u32],
+    ) -> Result<()>;
+
+    // Removes the clip pushed last
+    fn pop_clip(&mut self) -> Result<()>;
+
+    fn end(&mut self) -> Result<()>;
+}
+
+// The quad a rounded rectangle is drawn on, grown by a pixel so the anti-aliased edge is not cut off
+pub fn rounded_rect_quad(position: Vector, size: Vector) -> [Vector; 4] {
+    let min = position - 1f32;
+    let max = position + size + 1f32;
+
+    [
+        min,
+        Vector::new(max.x(), min.y()),
+        Vector::new(min.x(), max.y()),
+        max,
+    ]
+}
+
+/// The box a clip covers when it is a rectangle that stays axis-aligned under transform,
+/// which a scissor cuts exactly the same as the stencil would.
+pub fn clip_rect(vertices: &[Vector], indices: &[u32], transform: Transform) -> Option<(Vector, Vector)> {
+    if vertices.len() != 4 || indices.len() != 6 {
+        return None;
+    }
+
+    let points: Vec<Vector> = vertices.iter().map(|&v| transform.apply(v)).collect();
+    let (min, max) = tessellate::bounds(&points);
+
+    let corner = |p: &Vector| (p.x() == min.x() || p.x() == max.x()) && (p.y() == min.y() || p.y() == max.y());
+
+    // Both triangles together have to cover the whole box
+    let area: f32 = indices
+        .chunks(3)
+        .map(|i| ((points[i[1] as usize] - points[i[0] as usize]).cross(points[i[2] as usize] - points[i[0] as usize]) / 2f32).abs())
+        .sum();
+
+    if points.iter().all(corner) && area == (max.x() - min.x()) * (max.y() - min.y()) {
+        Some((min, max))
+    } else {
+        None
+    }
+}
+
+/// VulkanUploader is the prepare phase of a frame. It creates the buffers and textures
+/// of every draw without recording anything, so the recording pass after it finds all of
+/// them uploaded and no shape is left out of the first frame.
+pub struct VulkanUploader<'a> {
+    pub device: Arc<Device>,
+    pub queue: Arc<Queue>,
+    pub pipelines: &'a ShapesPipeline,
+    pub resolution: Vector,
+
+    // Set by transform, curves are tessellated for it before being uploaded
+    pub scale: f32,
+
+    // Texture uploads still in flight, the frame has to wait for them
+    pub future: Option<Box<dyn GpuFuture>>,
+}
+
+impl<'a> VulkanUploader<'a> {
+    pub fn new(
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        pipelines: &'a ShapesPipeline,
+        resolution: Vector,
+    ) -> VulkanUploader<'a> {
+        VulkanUploader {
+            device,
+            queue,
+            pipelines,
+            resolution,
+            scale: 1f32,
+            future: None,
+        }
+    }
+
+    /// Ends the prepare phase, returning the future the frame's command buffer has to wait on.
+    pub fn finish(self) -> Box<dyn GpuFuture> {
+        match self.future {
+            Some(f) => f,
+            None => sync::now(self.device.clone()).boxed(),
+        }
+    }
+
+    // Blocks until the texture uploads so far are done
+    pub fn wait(&mut self) -> Result<()> {
+        match self.future.take() {
+            Some(f) => {
+                f.then_signal_fence_and_flush()?.wait(None)?;
+                Ok(())
+            },
+            None => Ok(()),
+        }
+    }
+
+    // Returns the cached buffer, uploading the geometry first when there is none yet
+    fn upload(&self, buffer: &mut Option<Buffer>, vertices: &[Vector], indices: &[u32]) -> Result<Buffer> {
+        match buffer {
+            Some(b) => Ok(b.clone()),
+            None => {
+                let vertex_buffer = CpuAccessibleBuffer::from_iter(
+                    self.device.clone(), BufferUsage::all(), false,
+                    vertices.iter().cloned()
+                )?;
+
+                let index_buffer = CpuAccessibleBuffer::from_iter(
+                    self.device.clone(), BufferUsage::all(), false,
+                    indices.iter().cloned()
+                )?;
+
+                let b = Buffer::new(vertex_buffer, index_buffer);
+                *buffer = Some(b.clone());
+
+                Ok(b)
+            },
+        }
+    }
+
+    // Same as upload, for the per vertex colored geometry of a batch
+    fn upload_batch(&self, buffer: &mut Option<BatchBuffer>, vertices: &[ColoredVertex], indices: &[u32]) -> Result<BatchBuffer> {
+        match buffer {
+            Some(b) => Ok(b.clone()),
+            None => {
+                let vertex_buffer = CpuAccessibleBuffer::from_iter(
+                    self.device.clone(), BufferUsage::all(), false,
+                    vertices.iter().cloned()
+                )?;
+
+                let index_buffer = CpuAccessibleBuffer::from_iter(
+                    self.device.clone(), BufferUsage::all(), false,
+                    indices.iter().cloned()
+                )?;
+
+                let b = BatchBuffer::new(vertex_buffer, index_buffer);
+                *buffer = Some(b.clone());
+
+                Ok(b)
+            },
+        }
+    }
+
+    // Same as upload, but also creates the texture and the descriptor set the pipeline samples it with
+    fn upload_textured<V: Copy + Send + Sync + 'static>(&mut self,
+        buffer: &mut Option<TexturedBuffer<V>>,
+        vertices: &[V],
+        indices: &[u32],
+        image: &Frame,
+        pipeline: &Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+        address: SamplerAddressMode,
+    ) -> Result<TexturedBuffer<V>> {
+        match buffer {
+            Some(b) => Ok(b.clone()),
+            None => {
+                let vertex_buffer = CpuAccessibleBuffer::from_iter(
+                    self.device.clone(), BufferUsage::all(), false,
+                    vertices.iter().cloned()
+                )?;
+
+                let index_buffer = CpuAccessibleBuffer::from_iter(
+                    self.device.clone(), BufferUsage::all(), false,
+                    indices.iter().cloned()
+                )?;
+
+                let (texture, future) = ImmutableImage::from_iter(
+                    image.data.iter().cloned(),
+                    Dimensions::Dim2d { width: image.width, height: image.height },
+                    Format::R8G8B8A8Unorm,
+                    self.queue.clone(),
+                )?;
+
+                self.future = Some(match self.future.take() {
+                    Some(f) => f.join(future).boxed(),
+                    None => future.boxed(),
+                });
+
+                let sampler = Sampler::new(
+                    self.device.clone(),
+                    Filter::Linear,
+                    Filter::Linear,
+                    MipmapMode::Nearest,
+                    address,
+                    address,
+                    address,
+                    0.0, 1.0, 0.0, 0.0,
+                )?;
+
+                let layout = pipeline.descriptor_set_layout(0).unwrap().clone();
+
+                let set = Arc::new(
+                    PersistentDescriptorSet::start(layout)
+                        .add_sampled_image(texture, sampler)?
+                        .build()?
+                );
+
+                let b = TexturedBuffer::new(vertex_buffer, index_buffer, set);
+                *buffer = Some(b.clone());
+
+                Ok(b)
+            },
+        }
+    }
+
+    // Uploads the geometry of a gradient along with its ramp texture
+    fn upload_gradient(&mut self,
+        buffer: &mut Option<TexturedBuffer<Vector>>,
+        vertices: &[Vector],
+        indices: &[u32],
+        gradient: &Gradient,
+    ) -> Result<TexturedBuffer<Vector>> {
+        let pipeline = self.pipelines.gradient.get(BlendMode::Normal);
+
+        // Only bake the ramp when it is about to be uploaded
+        let ramp = match buffer {
+            Some(_) => Frame::new(0, 0, Vec::new()),
+            None => gradient.ramp(),
+        };
+
+        self.upload_textured(buffer, vertices, indices, &ramp, &pipeline, SamplerAddressMode::ClampToEdge)
+    }
+}
+
+impl<'a> Renderer for VulkanUploader<'a> {
+    fn resolution(&self) -> Vector {
+        self.resolution
+    }
+
+    fn begin(&mut self, _clear: Color) -> Result<()> {
+        Ok(())
+    }
+
+    fn transform(&mut self, transform: Transform) {
+        self.scale = transform.norm();
+    }
+
+    fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    fn blend(&mut self, _mode: BlendMode) {}
+
+    fn fill(&mut self,
+        buffer: &mut Option<Buffer>,
+        vertices: &[Vector],
+        indices: &[u32],
+        _color: Color,
+    ) -> Result<()> {
+        self.upload(buffer, vertices, indices)?;
+
+        Ok(())
+    }
+
+    fn rounded_rect(&mut self,
+        buffer: &mut Option<Buffer>,
+        position: Vector,
+        size: Vector,
+        _radius: [f32; 4],
+        _color: Color,
+    ) -> Result<()> {
+        self.upload(buffer, &rounded_rect_quad(position, size), &[0u32, 1u32, 2u32, 1u32, 3u32, 2u32])?;
+
+        Ok(())
+    }
+
+    fn textured(&mut self,
+        buffer: &mut Option<TexturedBuffer>,
+        vertices: &[TexturedVertex],
+        indices: &[u32],
+        image: &Frame,
+        repeat: bool,
+        _color: Color,
+    ) -> Result<()> {
+        let pipeline = self.pipelines.textured.get(BlendMode::Normal);
+
+        self.upload_textured(buffer, vertices, indices, image, &pipeline, address_mode(repeat))?;
+
+        Ok(())
+    }
+
+    fn gradient(&mut self,
+        buffer: &mut Option<TexturedBuffer<Vector>>,
+        vertices: &[Vector],
+        indices: &[u32],
+        gradient: &Gradient,
+        _span: GradientSpan,
+    ) -> Result<()> {
+        self.upload_gradient(buffer, vertices, indices, gradient)?;
+
+        Ok(())
+    }
+
+    fn batch(&mut self,
+        buffer: &mut Option<BatchBuffer>,
+        vertices: &[ColoredVertex],
+        indices: &[u32],
+    ) -> Result<()> {
+        self.upload_batch(buffer, vertices, indices)?;
+
+        Ok(())
+    }
+
+    fn push_clip(&mut self,
+        buffer: &mut Option<Buffer>,
+        vertices: &[Vector],
+        indices: &[u32],
+    ) -> Result<()> {
+        self.upload(buffer, vertices, indices)?;
+
+        Ok(())
+    }
+
+    fn pop_clip(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+// What pop_clip has to undo
+enum ClipState {
+    // The scissor from before the clip narrowed it
+    Scissor(Scissor),
+
+    // The stencil was raised inside the geometry, drawn again with its transform to lower it
+    Stencil(Buffer, Transform),
+}
+
+/// VulkanRenderer records draw calls into a command buffer using the ShapesPipeline.
+///
+/// The frame is expected to have gone through a VulkanUploader first. Anything that was
+/// not is uploaded while recording, and waited for before the render pass ends.
+pub struct VulkanRenderer<'a> {
+    pub builder: &'a mut AutoCommandBufferBuilder,
+    pub framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+    pub pipelines: &'a ShapesPipeline,
+    pub resolution: Vector,
+
+    // Samples per pixel of the render pass, with MSAA it has a resolved color attachment to clear
+    pub samples: u32,
+
+    // Copied from the surface, the scissor and the stencil reference change with the clips
+    pub dynamic_state: DynamicState,
+
+    // Set by transform, pushed along with every draw
+    pub transform: Transform,
+
+    // Set by blend, picks the pipeline of every draw
+    pub blend: BlendMode,
+
+    clips: Vec<ClipState>,
+
+    // Number of stencil clips the draws are inside, what the stencil has to equal for them to show
+    depth: u32,
+
+    pub uploader: VulkanUploader<'a>,
+}
+
+impl<'a> VulkanRenderer<'a> {
+    pub fn new(
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        builder: &'a mut AutoCommandBufferBuilder,
+        framebuffer: Arc<dyn FramebufferAbstract + Send + Sync>,
+        pipelines: &'a ShapesPipeline,
+        dynamic_state: &'a DynamicState,
+        resolution: Vector,
+        samples: u32,
+    ) -> VulkanRenderer<'a> {
+        let dimensions = framebuffer.dimensions();
+
+        let mut dynamic_state = dynamic_state.clone();
+        dynamic_state.scissors = Some(vec![Scissor {
+            origin: [0, 0],
+            dimensions: [dimensions[0], dimensions[1]],
+        }]);
+        dynamic_state.reference = Some(stencil_reference(0));
+
+        VulkanRenderer {
+            builder,
+            framebuffer,
+            pipelines,
+            resolution,
+            samples,
+            dynamic_state,
+            transform: Transform::identity(),
+            blend: BlendMode::Normal,
+            clips: Vec::new(),
+            depth: 0,
+            uploader: VulkanUploader::new(device, queue, pipelines, resolution),
+        }
+    }
+
+    // Draws clip geometry with one of the pipelines that only change the stencil
+    fn draw_stencil(&mut self,
+        pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+        b: &Buffer,
+        transform: Transform,
+    ) -> Result<()> {
+        self.builder.draw_indexed(
+            pipeline,
+            &self.dynamic_state,
+            vec![b.vertex_buffer.clone()],
+            b.index_buffer.clone(),
+            (),
+            vs::ty::PushConstantData {
+                linear: transform.linear(),
+                resolution: [self.resolution.x(), self.resolution.y()],
+                r: 0f32,
+                g: 0f32,
+                b: 0f32,
+                a: 0f32,
+                translation: transform.translation.position,
+            },
+        )?;
+
+        Ok(())
+    }
+}
+
+// Sampler addressing for Renderer::textured, clamped so scaled up edges do not bleed into each other
+fn address_mode(repeat: bool) -> SamplerAddressMode {
+    if repeat {
+        SamplerAddressMode::Repeat
+    } else {
+        SamplerAddressMode::ClampToEdge
+    }
+}
+
+fn stencil_reference(value: u32) -> DynamicStencilValue {
+    DynamicStencilValue {
+        face: StencilFaceFlags::StencilFrontAndBack,
+        value,
+    }
+}
+
+impl<'a> Renderer for VulkanRenderer<'a> {
+    fn resolution(&self) -> Vector {
+        self.resolution
+    }
+
+    fn begin(&mut self, clear: Color) -> Result<()> {
+        let clear = ClearValue::Float(clear.to_premultiplied_linear());
+        let stencil = ClearValue::DepthStencil((1f32, 0u32));
+
+        // One value per attachment of create_render_pass, the resolved color is not cleared
+        let clear_values = if self.samples > 1 {
+            vec![clear, ClearValue::None, stencil]
+        } else {
+            vec![clear, stencil]
+        };
+
+        self.builder.begin_render_pass(self.framebuffer.clone(), false, clear_values)?;
+
+        Ok(())
+    }
+
+    fn transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    fn scale(&self) -> f32 {
+        self.transform.norm()
+    }
+
+    fn blend(&mut self, mode: BlendMode) {
+        self.blend = mode;
+    }
+
+    fn fill(&mut self,
+        buffer: &mut Option<Buffer>,
+        vertices: &[Vector],
+        indices: &[u32],
+        color: Color,
+    ) -> Result<()> {
+        let b = self.uploader.upload(buffer, vertices, indices)?;
+        let col = color.to_premultiplied_linear();
+
+        self.builder.draw_indexed(
+            self.pipelines.default_fill.get(self.blend),
+            &self.dynamic_state,
+            vec![b.vertex_buffer.clone()],
+            b.index_buffer.clone(),
+            (),
+            vs::ty::PushConstantData {
+                resolution: [self.resolution.x(), self.resolution.y()],
+                r: col[0],
+                g: col[1],
+                b: col[2],
+                a: col[3],
+                linear: self.transform.linear(),
+                translation: self.transform.translation.position,
+            },
+        )?;
+
+        Ok(())
+    }
+
+    fn rounded_rect(&mut self,
+        buffer: &mut Option<Buffer>,
+        position: Vector,
+        size: Vector,
+        radius: [f32; 4],
+        color: Color,
+    ) -> Result<()> {
+        let b = self.uploader.upload(buffer, &rounded_rect_quad(position, size), &[0u32, 1u32, 2u32, 1u32, 3u32, 2u32])?;
+
+        self.builder.draw_indexed(
+            self.pipelines.rounded_rect.get(self.blend),
+            &self.dynamic_state,
+            vec![b.vertex_buffer.clone()],
+            b.index_buffer.clone(),
+            (),
+            rounded_rect_vs::ty::PushConstantData {
+                radius,
+                color: color.to_premultiplied_linear(),
+                resolution: [self.resolution.x(), self.resolution.y()],
+                origin: position.position,
+                size: size.position,
+                linear: self.transform.linear(),
+                translation: self.transform.translation.position,
+            },
+        )?;
+
+        Ok(())
+    }
+
+    fn textured(&mut self,
+        buffer: &mut Option<TexturedBuffer>,
+        vertices: &[TexturedVertex],
+        indices: &[u32],
+        image: &Frame,
+        repeat: bool,
+        color: Color,
+    ) -> Result<()> {
+        let pipeline = self.pipelines.textured.get(self.blend);
+        let b = self.uploader.upload_textured(buffer, vertices, indices, image, &pipeline, address_mode(repeat))?;
+
+        self.builder.draw_indexed(
+            pipeline,
+            &self.dynamic_state,
+            vec![b.vertex_buffer.clone()],
+            b.index_buffer.clone(),
+            b.set.clone(),
+            textured_vs::ty::PushConstantData {
+                color: color.to_premultiplied_linear(),
+                resolution: [self.resolution.x(), self.resolution.y()],
+                linear: self.transform.linear(),
+                translation: self.transform.translation.position,
+            },
+        )?;
+
+        Ok(())
+    }
+
+    fn gradient(&mut self,
+        buffer: &mut Option<TexturedBuffer<Vector>>,
+        vertices: &[Vector],
+        indices: &[u32],
+        gradient: &Gradient,
+        span: GradientSpan,
+    ) -> Result<()> {
+        let b = self.uploader.upload_gradient(buffer, vertices, indices, gradient)?;
+
+        self.builder.draw_indexed(
+            self.pipelines.gradient.get(self.blend),
+            &self.dynamic_state,
+            vec![b.vertex_buffer.clone()],
+            b.index_buffer.clone(),
+            b.set.clone(),
+            gradient_vs::ty::PushConstantData {
+                start: span.start.position,
+                end: span.end.position,
+                resolution: [self.resolution.x(), self.resolution.y()],
+                linear: self.transform.linear(),
+                translation: self.transform.translation.position,
+                radial: span.radial as u32,
+            },
+        )?;
+
+        Ok(())
+    }
+
+    fn batch(&mut self,
+        buffer: &mut Option<BatchBuffer>,
+        vertices: &[ColoredVertex],
+        indices: &[u32],
+    ) -> Result<()> {
+        let b = self.uploader.upload_batch(buffer, vertices, indices)?;
+
+        self.builder.draw_indexed(
+            self.pipelines.batch.get(self.blend),
+            &self.dynamic_state,
+            vec![b.vertex_buffer.clone()],
+            b.index_buffer.clone(),
+            (),
+            batch_vs::ty::PushConstantData {
+                resolution: [self.resolution.x(), self.resolution.y()],
+                linear: self.transform.linear(),
+                translation: self.transform.translation.position,
+            },
+        )?;
+
+        Ok(())
+    }
+
+    fn push_clip(&mut self,
+        buffer: &mut Option<Buffer>,
+        vertices: &[Vector],
+        indices: &[u32],
+    ) -> Result<()> {
+        let previous = self.dynamic_state.scissors.as_ref().unwrap()[0].clone();
+
+        match clip_rect(vertices, indices, self.transform) {
+            Some((min, max)) => {
+                // Pixels whose center lands inside the rectangle, the same ones the stencil would keep
+                let x0 = (min.x().ceil() as i32).max(previous.origin[0]);
+                let y0 = (min.y().ceil() as i32).max(previous.origin[1]);
+                let x1 = (max.x().ceil() as i32).min(previous.origin[0] + previous.dimensions[0] as i32);
+                let y1 = (max.y().ceil() as i32).min(previous.origin[1] + previous.dimensions[1] as i32);
+
+                self.dynamic_state.scissors = Some(vec![Scissor {
+                    origin: [x0, y0],
+                    dimensions: [(x1 - x0).max(0) as u32, (y1 - y0).max(0) as u32],
+                }]);
+
+                self.clips.push(ClipState::Scissor(previous));
+            },
+            None => {
+                let b = self.uploader.upload(buffer, vertices, indices)?;
+                let pipeline = self.pipelines.clip_push.clone();
+
+                self.draw_stencil(pipeline, &b, self.transform)?;
+
+                self.depth += 1;
+                self.dynamic_state.reference = Some(stencil_reference(self.depth));
+
+                self.clips.push(ClipState::Stencil(b, self.transform));
+            },
+        };
+
+        Ok(())
+    }
+
+    fn pop_clip(&mut self) -> Result<()> {
+        match self.clips.pop() {
+            Some(ClipState::Scissor(previous)) => {
+                self.dynamic_state.scissors = Some(vec![previous]);
+            },
+            Some(ClipState::Stencil(b, transform)) => {
+                let pipeline = self.pipelines.clip_pop.clone();
+
+                self.draw_stencil(pipeline, &b, transform)?;
+
+                self.depth -= 1;
+                self.dynamic_state.reference = Some(stencil_reference(self.depth));
+            },
+            None => (),
+        };
+
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<()> {
+        self.builder.end_render_pass()?;
+
+        // Textures missed by the prepare phase have to be ready before the frame is submitted
+        self.uploader.wait()
+    }
+}
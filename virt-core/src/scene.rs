@@ -0,0 +1,706 @@
+use crate::render::{Renderer, BlendMode};
+use crate::software::SoftwareRenderer;
+use crate::effects::{self, Layer};
+use crate::buffer::TexturedBuffer;
+use crate::clip::{self, Clip, Mask};
+use crate::color::Color;
+use crate::shape::Shape;
+use crate::tools::Button;
+use crate::vector::Vector;
+use crate::transform::Transform;
+use crate::error::{CoreError, Result};
+
+/// Layer of the nodes that are not given one.
+pub const DEFAULT_LAYER: &str = "default";
+
+/// NodeId identifies a node for as long as it is part of the scene, ids are never reused.
+pub type NodeId = usize;
+
+#[derive(Debug)]
+pub enum NodeKind {
+    // Only holds children
+    Group,
+    Shape(Box<dyn Shape>),
+    Button(Button),
+}
+
+/// Node is one element of the scene, placed relative to its parent.
+#[derive(Debug)]
+pub struct Node {
+    pub id: NodeId,
+
+    // Name from the config, to find the node again
+    pub name: Option<String>,
+
+    pub kind: NodeKind,
+
+    pub parent: Option<NodeId>,
+    pub children: Vec<NodeId>,
+
+    // Placement relative to the parent
+    pub transform: Transform,
+
+    // Hidden nodes and everything below them are neither drawn nor hit
+    pub visible: bool,
+
+    // Siblings are drawn in increasing z, and in the order they were added when equal
+    pub z: i32,
+
+    // Index into the layers of the scene, None inherits the layer of the parent
+    pub layer: Option<usize>,
+
+    // Restricts everything below the node, its own shape is not clipped
+    pub clip: Option<Clip>,
+
+    // Fades everything below the node, which is then drawn as one texture on the layer of the node
+    pub mask: Option<Mask>,
+
+    // Fades the node and everything below it as a whole, from 0 to 1
+    pub opacity: f32,
+
+    // How the node is drawn onto what is already there
+    pub blend: BlendMode,
+
+    // Blur radius in pixels of what was drawn behind the node, seen through its shape, 0 for none
+    pub backdrop: f32,
+
+    // Revision of the scene the node was last handed out mutably at
+    revision: u64,
+
+    // Last offscreen rendering of a composited node
+    composite: Option<Composite>,
+
+    // Last blurred backdrop of the node
+    backdrop_layer: Option<Composite>,
+}
+
+// What a node drew offscreen, reused until something it was drawn from changes
+#[derive(Debug)]
+struct Composite {
+    layer: Layer,
+    buffer: Option<TexturedBuffer>,
+
+    // Newest revision of what was drawn, the size of the offscreen and the transform onto
+    // it when it was drawn. A faded node is drawn where it lands, so moving it draws it again.
+    revision: u64,
+    size: (u32, u32),
+    base: Transform,
+}
+
+impl Node {
+    fn new(id: NodeId, parent: Option<NodeId>, kind: NodeKind) -> Node {
+        Node {
+            id,
+            name: None,
+            kind,
+            parent,
+            children: Vec::new(),
+            transform: Transform::identity(),
+            visible: true,
+            z: 0,
+            layer: None,
+            clip: None,
+            mask: None,
+            opacity: 1f32,
+            blend: BlendMode::Normal,
+            backdrop: 0f32,
+            revision: 0,
+            composite: None,
+            backdrop_layer: None,
+        }
+    }
+
+    // Masked and faded nodes are drawn offscreen with everything below them first
+    fn composited(&self) -> bool {
+        self.mask.is_some() || self.opacity < 1f32
+    }
+
+    // The shape drawn by the node, groups have none
+    pub fn shape(&self) -> Option<&dyn Shape> {
+        match &self.kind {
+            NodeKind::Group => None,
+            NodeKind::Shape(s) => Some(&**s),
+            NodeKind::Button(b) => Some(&*b.shape),
+        }
+    }
+
+    pub fn shape_mut(&mut self) -> Option<&mut dyn Shape> {
+        match &mut self.kind {
+            NodeKind::Group => None,
+            NodeKind::Shape(s) => Some(&mut **s),
+            NodeKind::Button(b) => Some(&mut *b.shape),
+        }
+    }
+}
+
+/// Scene is the retained tree of everything a widget draws. Drawing, hit testing and
+/// changes made at runtime all go through it, whoever changes it invalidates the widget.
+#[derive(Debug)]
+pub struct Scene {
+    // Indexed by NodeId, removed nodes leave a hole so ids stay stable
+    nodes: Vec<Option<Node>>,
+
+    pub root: NodeId,
+
+    // Names of the layers from bottom to top. Every node of a lower layer is drawn, and hit,
+    // beneath every node of a higher one, wherever they are in the tree.
+    pub layers: Vec<String>,
+
+    // Counts the times nodes were handed out mutably, so composites know when they are stale
+    revision: u64,
+}
+
+impl Scene {
+    pub fn new() -> Scene {
+        Scene {
+            nodes: vec![Some(Node::new(0, None, NodeKind::Group))],
+            root: 0,
+            layers: vec![DEFAULT_LAYER.to_string()],
+            revision: 0,
+        }
+    }
+
+    /// Replaces the layers, bottom to top. The default layer is put at the bottom unless it is
+    /// named, so nodes without a layer land where it is in the list.
+    pub fn set_layers(&mut self, layers: &[String]) {
+        self.layers = layers.to_vec();
+
+        if !self.layers.iter().any(|l| l == DEFAULT_LAYER) {
+            self.layers.insert(0, DEFAULT_LAYER.to_string());
+        }
+    }
+
+    pub fn layer(&self, name: &str) -> Result<usize> {
+        self.layers
+            .iter()
+            .position(|l| l == name)
+            .ok_or_else(|| CoreError::LayerNotFound(name.to_string()))
+    }
+
+    /// Adds a node as the last child of parent.
+    pub fn add(&mut self, parent: NodeId, kind: NodeKind) -> Result<NodeId> {
+        let id = self.nodes.len();
+
+        match self.get_mut(parent) {
+            Some(p) => p.children.push(id),
+            None => return Err(CoreError::NodeNotFound(parent)),
+        };
+
+        self.nodes.push(Some(Node::new(id, Some(parent), kind)));
+        self.get_mut(id);
+
+        Ok(id)
+    }
+
+    /// Removes a node together with everything below it. Removing the root only removes its children.
+    pub fn remove(&mut self, id: NodeId) -> Result<()> {
+        let (parent, children) = match self.get(id) {
+            Some(n) => (n.parent, n.children.clone()),
+            None => return Err(CoreError::NodeNotFound(id)),
+        };
+
+        for child in children {
+            self.remove(child)?;
+        }
+
+        match parent {
+            Some(p) => {
+                if let Some(p) = self.get_mut(p) {
+                    p.children.retain(|&c| c != id);
+                }
+
+                self.nodes[id] = None;
+            },
+            None => (),
+        };
+
+        Ok(())
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&Node> {
+        self.nodes.get(id).and_then(|n| n.as_ref())
+    }
+
+    /// Mutable access to a node. Whatever is done with it counts as a change, composites the
+    /// node is part of are drawn again.
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        self.revision += 1;
+        let revision = self.revision;
+
+        self.nodes.get_mut(id).and_then(|n| n.as_mut()).map(|n| {
+            n.revision = revision;
+            n
+        })
+    }
+
+    /// Finds the first node with the given name.
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.nodes
+            .iter()
+            .flatten()
+            .find(|n| n.name.as_ref().map(|s| s.as_str()) == Some(name))
+            .map(|n| n.id)
+    }
+
+    // Every node in the scene, in no particular order
+    pub fn nodes(&self) -> impl Iterator<Item = &Node> {
+        self.nodes.iter().flatten()
+    }
+
+    // Same as nodes, counting as a change of every node like get_mut does
+    pub fn nodes_mut(&mut self) -> impl Iterator<Item = &mut Node> {
+        self.revision += 1;
+        let revision = self.revision;
+
+        self.nodes.iter_mut().flatten().map(move |n| {
+            n.revision = revision;
+            n
+        })
+    }
+
+    /// Transform of a node combined with those of all of its parents.
+    pub fn world_transform(&self, id: NodeId) -> Transform {
+        self.relative_transform(id, None)
+    }
+
+    // Transform of a node combined with those of its parents below above
+    fn relative_transform(&self, id: NodeId, above: Option<NodeId>) -> Transform {
+        let mut transform = Transform::identity();
+        let mut current = self.get(id);
+
+        while let Some(n) = current {
+            if Some(n.id) == above {
+                break;
+            }
+
+            transform = n.transform * transform;
+            current = n.parent.and_then(|p| self.get(p));
+        }
+
+        transform
+    }
+
+    // Parents of a node that clip it, outermost first, stopping at within
+    fn clips_above(&self, id: NodeId, within: Option<NodeId>) -> Vec<NodeId> {
+        let mut clips = Vec::new();
+
+        if Some(id) == within {
+            return clips;
+        }
+
+        let mut current = self.get(id).and_then(|n| n.parent).and_then(|p| self.get(p));
+
+        while let Some(n) = current {
+            if n.clip.is_some() {
+                clips.push(n.id);
+            }
+
+            if Some(n.id) == within {
+                break;
+            }
+
+            current = n.parent.and_then(|p| self.get(p));
+        }
+
+        clips.reverse();
+        clips
+    }
+
+    // The closest composited parent, the one a node is drawn by
+    fn composite_above(&self, id: NodeId) -> Option<NodeId> {
+        let mut current = self.get(id).and_then(|n| n.parent).and_then(|p| self.get(p));
+
+        while let Some(n) = current {
+            if n.composited() {
+                return Some(n.id);
+            }
+
+            current = n.parent.and_then(|p| self.get(p));
+        }
+
+        None
+    }
+
+    // Newest revision of a node and everything below it, hidden nodes included
+    fn subtree_revision(&self, id: NodeId) -> u64 {
+        match self.get(id) {
+            Some(n) => n.children
+                .iter()
+                .map(|&c| self.subtree_revision(c))
+                .fold(n.revision, u64::max),
+            None => 0,
+        }
+    }
+
+    /// Whether v, in widget space, is inside every clip above a node.
+    pub fn unclipped(&self, id: NodeId, v: Vector) -> bool {
+        self.clips_above(id, None).into_iter().all(|c| {
+            match (self.get(c).and_then(|n| n.clip.as_ref()), self.world_transform(c).inverse()) {
+                (Some(clip), Some(inverse)) => clip.contains(inverse.apply(v)),
+                _ => false,
+            }
+        })
+    }
+
+    /// Smallest box, as min and max, holding the outlines of every shape at or below a node,
+    /// in the space the node's own transform maps from. None when there are no shapes.
+    pub fn bounds(&self, id: NodeId) -> Option<(Vector, Vector)> {
+        let mut bounds = None;
+
+        self.extend_bounds(id, Transform::identity(), &mut bounds);
+
+        bounds
+    }
+
+    fn extend_bounds(&self, id: NodeId, transform: Transform, bounds: &mut Option<(Vector, Vector)>) {
+        let node = match self.get(id) {
+            Some(n) => n,
+            None => return,
+        };
+
+        for v in node.shape().map(|s| s.outline()).unwrap_or_default() {
+            let v = transform.apply(v);
+
+            *bounds = Some(match *bounds {
+                Some((min, max)) => (
+                    Vector::new(min.x().min(v.x()), min.y().min(v.y())),
+                    Vector::new(max.x().max(v.x()), max.y().max(v.y())),
+                ),
+                None => (v, v),
+            });
+        }
+
+        for &child in node.children.iter() {
+            if let Some(c) = self.get(child) {
+                self.extend_bounds(child, transform * c.transform, bounds);
+            }
+        }
+    }
+
+    /// Visible nodes in the order they are drawn, together with their world transform.
+    /// Layers come first, then parents before their children and siblings by z.
+    pub fn draw_order(&self) -> Vec<(NodeId, Transform)> {
+        let mut order = Vec::new();
+        let default = self.layer(DEFAULT_LAYER).unwrap_or(0);
+
+        self.collect(self.root, Transform::identity(), default, &mut order);
+
+        // Stable, so the tree order is kept inside every layer
+        order.sort_by_key(|&(_, _, layer)| layer);
+
+        order.into_iter().map(|(id, transform, _)| (id, transform)).collect()
+    }
+
+    fn collect(&self, id: NodeId, parent_transform: Transform, parent_layer: usize, order: &mut Vec<(NodeId, Transform, usize)>) {
+        let node = match self.get(id) {
+            Some(n) if n.visible => n,
+            _ => return,
+        };
+
+        let transform = parent_transform * node.transform;
+        let layer = node.layer.unwrap_or(parent_layer);
+        order.push((id, transform, layer));
+
+        for child in self.children_by_z(node) {
+            self.collect(child, transform, layer, order);
+        }
+    }
+
+    fn children_by_z(&self, node: &Node) -> Vec<NodeId> {
+        let mut children: Vec<&Node> = node.children.iter().filter_map(|&c| self.get(c)).collect();
+
+        // Stable, so equal z keeps the order the children were added in
+        children.sort_by_key(|c| c.z);
+
+        children.into_iter().map(|c| c.id).collect()
+    }
+
+    /// Visible nodes whose shape contains v, the topmost first. v is taken back into the
+    /// space of each shape, so transformed shapes are hit where they are drawn, and
+    /// whatever a clip cuts away is not hit.
+    pub fn hits(&self, v: Vector) -> Vec<NodeId> {
+        self.draw_order()
+            .iter()
+            .rev()
+            .filter(|(id, transform)| match (self.get(*id).and_then(|n| n.shape()), transform.inverse()) {
+                (Some(s), Some(inverse)) => s.contains(inverse.apply(v)) && self.unclipped(*id, v),
+                _ => false,
+            })
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    pub fn draw(&mut self, renderer: &mut dyn Renderer) -> Result<()> {
+        // Nodes below a composited node are drawn by it
+        let order: Vec<(NodeId, Transform)> = self.draw_order()
+            .into_iter()
+            .filter(|&(id, _)| self.composite_above(id).is_none())
+            .collect();
+
+        self.draw_nodes(&order, None, Transform::identity(), renderer)?;
+
+        renderer.transform(Transform::identity());
+        renderer.blend(BlendMode::Normal);
+
+        Ok(())
+    }
+
+    // Draws the nodes in order, pushing and popping the clips above them as they change.
+    // within is the composited node being drawn offscreen, base the transform onto its offscreen.
+    fn draw_nodes(&mut self,
+        order: &[(NodeId, Transform)],
+        within: Option<NodeId>,
+        base: Transform,
+        renderer: &mut dyn Renderer,
+    ) -> Result<()> {
+        // Clips pushed on the renderer, outermost first
+        let mut pushed: Vec<NodeId> = Vec::new();
+
+        for (i, &(id, transform)) in order.iter().enumerate() {
+            let clips = self.clips_above(id, within);
+            let common = pushed.iter().zip(clips.iter()).take_while(|(a, b)| a == b).count();
+
+            while pushed.len() > common {
+                pushed.pop();
+                renderer.pop_clip()?;
+            }
+
+            for &c in clips[common..].iter() {
+                renderer.transform(base * self.relative_transform(c, within));
+
+                if let Some(clip) = self.nodes[c].as_mut().and_then(|n| n.clip.as_mut()) {
+                    clip.push(renderer)?;
+                }
+
+                pushed.push(c);
+            }
+
+            if Some(id) != within && self.get(id).map(|n| n.composited()).unwrap_or(false) {
+                self.draw_composited(id, transform, renderer)?;
+                continue;
+            }
+
+            if self.get(id).map(|n| n.backdrop > 0f32 && n.shape().is_some()).unwrap_or(false) {
+                self.draw_backdrop(&order[..i], id, transform, within, base, renderer)?;
+            }
+
+            if let Some(node) = self.nodes[id].as_mut() {
+                let blend = node.blend;
+
+                if let Some(shape) = node.shape_mut() {
+                    renderer.transform(transform);
+                    renderer.blend(blend);
+                    shape.draw(renderer)?;
+                }
+            }
+        }
+
+        while pushed.pop().is_some() {
+            renderer.pop_clip()?;
+        }
+
+        Ok(())
+    }
+
+    // Renders a node and everything below it offscreen, then draws the result as one texture
+    // through the mask and faded by the opacity of the node. The rendering is kept until something
+    // at or below the node changes, so the prepare and the recording pass and every frame after
+    // them share it.
+    fn draw_composited(&mut self, id: NodeId, transform: Transform, renderer: &mut dyn Renderer) -> Result<()> {
+        let node = match self.get(id) {
+            Some(n) if n.opacity > 0f32 => n,
+            _ => return Ok(()),
+        };
+
+        // A mask is rendered in the space of the node over its rectangle, anything else where
+        // it lands on the renderer. base takes the node onto the offscreen, placement the
+        // offscreen back onto the renderer.
+        let (width, height, base, placement) = match &node.mask {
+            Some(m) => (
+                m.size.x().ceil().max(1f32) as u32,
+                m.size.y().ceil().max(1f32) as u32,
+                Transform::translate(Vector::default() - m.position),
+                transform * Transform::translate(m.position),
+            ),
+            None => {
+                let resolution = renderer.resolution();
+                (resolution.x() as u32, resolution.y() as u32, transform, Transform::identity())
+            },
+        };
+
+        let revision = self.subtree_revision(id);
+        let size = (width, height);
+
+        let cached = node.composite
+            .as_ref()
+            .map(|c| c.revision == revision && c.size == size && c.base == base)
+            .unwrap_or(false);
+
+        if !cached {
+            self.render_composite(id, base, size, revision)?;
+        }
+
+        let node = match self.nodes[id].as_mut() {
+            Some(n) => n,
+            None => return Ok(()),
+        };
+
+        let tint = Color::new(255, 255, 255, (node.opacity.min(1f32) * u8::MAX as f32).round() as u8);
+
+        renderer.transform(placement);
+        renderer.blend(node.blend);
+
+        match &mut node.composite {
+            Some(c) => c.layer.draw(renderer, &mut c.buffer, Vector::default(), tint),
+            None => Ok(()),
+        }
+    }
+
+    // Blurs what the nodes behind drew, cuts it to the shape of a node and draws it beneath
+    // the shape, so the shape looks like frosted glass. The blur is kept until the node or
+    // anything behind it changes.
+    fn draw_backdrop(&mut self,
+        behind: &[(NodeId, Transform)],
+        id: NodeId,
+        transform: Transform,
+        within: Option<NodeId>,
+        base: Transform,
+        renderer: &mut dyn Renderer,
+    ) -> Result<()> {
+        let resolution = renderer.resolution();
+        let size = (resolution.x() as u32, resolution.y() as u32);
+
+        // Composited nodes behind draw everything below them, the children of the others
+        // count too so hiding one of them is noticed
+        let revision = behind
+            .iter()
+            .map(|&(n, _)| match self.get(n) {
+                Some(node) if node.composited() && Some(n) != within => self.subtree_revision(n),
+                Some(node) => node.children
+                    .iter()
+                    .filter_map(|&c| self.get(c))
+                    .map(|c| c.revision)
+                    .fold(node.revision, u64::max),
+                None => 0,
+            })
+            .fold(self.get(id).map(|n| n.revision).unwrap_or(0), u64::max);
+
+        let cached = self.get(id)
+            .and_then(|n| n.backdrop_layer.as_ref())
+            .map(|c| c.revision == revision && c.size == size && c.base == transform)
+            .unwrap_or(false);
+
+        if !cached {
+            let mut offscreen = SoftwareRenderer::new(size.0, size.1);
+
+            offscreen.begin(Color::default())?;
+            self.draw_nodes(behind, within, base, &mut offscreen)?;
+            offscreen.end()?;
+
+            let node = match self.nodes[id].as_mut() {
+                Some(n) => n,
+                None => return Ok(()),
+            };
+
+            effects::blur(&mut offscreen.pixels, size.0, size.1, node.backdrop);
+
+            // Covered by every triangle the shape draws, whatever its color
+            let mesh = match node.shape_mut() {
+                Some(shape) => clip::capture(shape, resolution, transform)?,
+                None => return Ok(()),
+            };
+
+            let mut cover = SoftwareRenderer::new(size.0, size.1);
+
+            cover.begin(Color::default())?;
+            cover.fill(&mut None, &mesh.vertices, &mesh.indices, Color::new(255, 255, 255, 255))?;
+            cover.end()?;
+
+            for (c, p) in cover.pixels.iter_mut().zip(offscreen.pixels.iter()) {
+                let a = c[3];
+                *c = [p[0] * a, p[1] * a, p[2] * a, p[3] * a];
+            }
+
+            let layer = Layer::crop(&cover, 0f32);
+
+            let buffer = match node.backdrop_layer.take() {
+                Some(c) if c.layer.image == layer.image && c.layer.position == layer.position => c.buffer,
+                _ => None,
+            };
+
+            node.backdrop_layer = Some(Composite {
+                layer,
+                buffer,
+                revision,
+                size,
+                base: transform,
+            });
+        }
+
+        let node = match self.nodes[id].as_mut() {
+            Some(n) => n,
+            None => return Ok(()),
+        };
+
+        renderer.transform(Transform::identity());
+        renderer.blend(BlendMode::Normal);
+
+        match &mut node.backdrop_layer {
+            Some(c) => c.layer.draw(renderer, &mut c.buffer, Vector::default(), Color::new(255, 255, 255, 255)),
+            None => Ok(()),
+        }
+    }
+
+    // Draws a composited node and everything below it onto an offscreen of size, through its mask
+    fn render_composite(&mut self, id: NodeId, base: Transform, size: (u32, u32), revision: u64) -> Result<()> {
+        let node = match self.get(id) {
+            Some(n) => n,
+            None => return Ok(()),
+        };
+
+        // Layers below a composited node are ignored, only the tree and z order the content
+        let mut order = vec![(id, base, 0)];
+        for child in self.children_by_z(node) {
+            self.collect(child, base, 0, &mut order);
+        }
+
+        let order: Vec<(NodeId, Transform)> = order
+            .into_iter()
+            .filter(|&(n, _, _)| n == id || self.composite_above(n) == Some(id))
+            .map(|(n, t, _)| (n, t))
+            .collect();
+
+        let mut offscreen = SoftwareRenderer::new(size.0, size.1);
+
+        offscreen.begin(Color::default())?;
+        self.draw_nodes(&order, Some(id), base, &mut offscreen)?;
+        offscreen.end()?;
+
+        let node = match self.nodes[id].as_mut() {
+            Some(n) => n,
+            None => return Ok(()),
+        };
+
+        if let Some(m) = &node.mask {
+            m.apply(&mut offscreen);
+        }
+
+        let layer = Layer::crop(&offscreen, 0f32);
+
+        // Only uploaded again when the rendering turned out different
+        let buffer = match node.composite.take() {
+            Some(c) if c.layer.image == layer.image && c.layer.position == layer.position => c.buffer,
+            _ => None,
+        };
+
+        node.composite = Some(Composite {
+            layer,
+            buffer,
+            revision,
+            size,
+            base,
+        });
+
+        Ok(())
+    }
+}
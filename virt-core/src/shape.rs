@@ -1,38 +1,114 @@
 use std::ops::{Add, Sub, Mul, Div};
 use std::fmt::Debug;
 use std::sync::Arc;
+use std::f32::consts::PI;
 
-use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
-use vulkano::buffer::{CpuAccessibleBuffer, BufferUsage};
-use vulkano::device::Device;
-
-use crate::pipelines::{ShapesPipeline, vs};
-use crate::buffer::Buffer;
+use crate::render::Renderer;
+use crate::buffer::{Buffer, TexturedBuffer, TexturedVertex};
+use crate::frame::Frame;
 use crate::vector::Vector;
 use crate::color::Color;
+use crate::tessellate;
+use crate::tessellate::{Mesh, LineJoin, LineCap};
+use crate::path::{self, Contour};
+use crate::text::TextLayout;
+use crate::gradient::Gradient;
 use crate::error::{CoreError, Result};
 
 pub trait Shape : Debug + Send + Sync {
     fn center(&self) -> Vector;
     fn area(&self) -> f32;
 
+    // Closed outline of the shape, stroked when drawn with ShapeFormat::Line
+    fn outline(&self) -> Vec<Vector>;
+
     fn color(&mut self, c: Color);
+    // Fills with a gradient instead of the color, shapes that are not filled ignore it
+    fn gradient(&mut self, g: Gradient);
     fn format(&mut self, f: ShapeFormat);
 
     fn contains(&self, v: Vector) -> bool;
 
-    fn draw(&mut self,
-        device: Arc<Device>,
-        builder: &mut AutoCommandBufferBuilder,
-        pipelines: &ShapesPipeline,
-        dynamic_state: &DynamicState,
-        resolution: Vector,
-    );
+    fn draw(&mut self, renderer: &mut dyn Renderer) -> Result<()>;
 }
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum ShapeFormat {
     Fill,
+    Line(Stroke),
+    FillLine(Stroke),
+}
+
+impl ShapeFormat {
+    pub fn fills(&self) -> bool {
+        match self {
+            ShapeFormat::Fill | ShapeFormat::FillLine(_) => true,
+            ShapeFormat::Line(_) => false,
+        }
+    }
+
+    pub fn stroke(&self) -> Option<Stroke> {
+        match *self {
+            ShapeFormat::Fill => None,
+            ShapeFormat::Line(s) | ShapeFormat::FillLine(s) => Some(s),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Stroke {
+    pub width: f32,
+    pub join: LineJoin,
+    pub cap: LineCap,
+    pub color: Color,
+}
+
+impl Stroke {
+    pub fn new(width: f32, color: Color) -> Stroke {
+        Stroke {
+            width,
+            join: LineJoin::Miter,
+            cap: LineCap::Butt,
+            color,
+        }
+    }
+}
+
+// Fills with the gradient when there is one, placed over the bounds of vertices,
+// otherwise with the solid color
+fn fill_paint(
+    renderer: &mut dyn Renderer,
+    buffer: &mut Option<Buffer>,
+    gradient_buffer: &mut Option<TexturedBuffer<Vector>>,
+    vertices: &[Vector],
+    indices: &[u32],
+    color: Color,
+    gradient: &Option<Gradient>,
+) -> Result<()> {
+    match gradient {
+        Some(g) => {
+            let (min, max) = tessellate::bounds(vertices);
+            renderer.gradient(gradient_buffer, vertices, indices, g, g.span(min, max))
+        },
+        None => renderer.fill(buffer, vertices, indices, color),
+    }
+}
+
+// Draws the outline of a shape when its format has a stroke
+fn draw_stroke(
+    renderer: &mut dyn Renderer,
+    buffer: &mut Option<Buffer>,
+    outline: &[Vector],
+    closed: bool,
+    format: ShapeFormat,
+) -> Result<()> {
+    match format.stroke() {
+        Some(s) => {
+            let mesh = tessellate::stroke(outline, closed, s.width, s.join, s.cap);
+            renderer.fill(buffer, &mesh.vertices, &mesh.indices, s.color)
+        },
+        None => Ok(()),
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -40,11 +116,17 @@ pub struct Rectangle {
     pub position: Vector,
     pub wh: Vector,
 
+    // Corner radii, top left, top right, bottom right, bottom left
+    pub radius: [f32; 4],
+
     pub color: Color,
+    pub gradient: Option<Gradient>,
 
     pub format: ShapeFormat,
 
     pub buffer: Option<Buffer>,
+    pub gradient_buffer: Option<TexturedBuffer<Vector>>,
+    pub stroke_buffer: Option<Buffer>,
 }
 
 impl Rectangle {
@@ -52,11 +134,49 @@ impl Rectangle {
         Rectangle {
             position: Vector::new(x, y),
             wh: Vector::new(w, h),
+            radius: [0f32; 4],
             color: Color::default(),
+            gradient: None,
             format: ShapeFormat::Fill,
             buffer: None,
+            gradient_buffer: None,
+            stroke_buffer: None,
+        }
+    }
+
+    // Sets the corner radii, clamped so opposite corners never overlap
+    pub fn radius(&mut self, radius: [f32; 4]) {
+        let max = self.wh.x().min(self.wh.y()) / 2f32;
+
+        for (r, v) in self.radius.iter_mut().zip(radius.iter()) {
+            *r = v.max(0f32).min(max);
         }
+
+        // The corners change the geometry of the fill and the stroke
+        self.buffer = None;
+        self.gradient_buffer = None;
+        self.stroke_buffer = None;
     }
+
+    pub fn is_rounded(&self) -> bool {
+        self.radius.iter().any(|&r| r > 0f32)
+    }
+}
+
+/// Signed distance from p to the edge of a rounded rectangle centered on 0,0,
+/// negative inside. radius is top left, top right, bottom right, bottom left.
+pub fn rounded_rect_distance(p: Vector, half: Vector, radius: [f32; 4]) -> f32 {
+    let r = match (p.x() > 0f32, p.y() > 0f32) {
+        (false, false) => radius[0],
+        (true, false) => radius[1],
+        (true, true) => radius[2],
+        (false, true) => radius[3],
+    };
+
+    let q = Vector::new(p.x().abs() - half.x() + r, p.y().abs() - half.y() + r);
+    let outside = Vector::new(q.x().max(0f32), q.y().max(0f32));
+
+    q.x().max(q.y()).min(0f32) + outside.hypot() - r
 }
 
 impl Shape for Rectangle {
@@ -67,91 +187,59 @@ impl Shape for Rectangle {
         self.wh.x() * self.wh.y()
     }
 
+    fn outline(&self) -> Vec<Vector> {
+        tessellate::rounded_rect(self.position, self.wh, self.radius)
+    }
+
     fn color(&mut self, c: Color) {
         self.color = c;
     }
+    // The ramp and the stroke are baked into their buffers, drop them so the change shows
+    fn gradient(&mut self, g: Gradient) {
+        self.gradient = Some(g);
+        self.gradient_buffer = None;
+    }
     fn format(&mut self, f: ShapeFormat) {
         self.format = f;
+        self.stroke_buffer = None;
     }
 
     fn contains(&self, v: Vector) -> bool {
+        if self.is_rounded() {
+            let half = self.wh / 2f32;
+            return rounded_rect_distance(v - self.position - half, half, self.radius) <= 0f32;
+        }
+
         !(v.x() < self.position.x() || v.x() > self.position.x() + self.wh.x() || v.y() < self.position.y() || v.y() > self.position.y() + self.wh.y())
     }
 
-    fn draw(&mut self,
-        device: Arc<Device>,
-        builder: &mut AutoCommandBufferBuilder,
-        pipelines: &ShapesPipeline,
-        dynamic_state: &DynamicState,
-        resolution: Vector,
-    ) {
-        match &self.buffer {
-            Some(b) => {
-                let col = self.color.to_float();
-        
-                builder.draw_indexed(
-                    pipelines.default_fill.clone(), 
-                    dynamic_state, 
-                    vec![b.vertex_buffer.clone()], 
-                    b.index_buffer.clone(),
-                    (),
-                    vs::ty::PushConstantData {
-                        resolution: [resolution.x(), resolution.y()],
-                        r: col[0],
-                        g: col[1],
-                        b: col[2],
-                        a: col[3],
-                    }
-                ).unwrap();
-            },
-            None => {
-                let vertex_buffer = CpuAccessibleBuffer::from_iter(
-                    device.clone(), BufferUsage::all(), false, 
-                    vec![   self.position,
-                        self.position + Vector::new(self.wh.x(), 0f32),
-                        self.position + Vector::new(0f32, self.wh.y()),
-                        self.position + self.wh
-                    ].iter().cloned()
-                ).unwrap();
-        
-                let index_buffer = CpuAccessibleBuffer::from_iter(
-                    device.clone(), BufferUsage::all(), false, 
-                    vec![0u16, 1u16, 2u16, 1u16, 3u16, 2u16].iter().cloned()
-                ).unwrap();
-        
-                self.buffer = Some(Buffer::new(vertex_buffer, index_buffer));
+    fn draw(&mut self, renderer: &mut dyn Renderer) -> Result<()> {
+        if self.format.fills() {
+            if self.is_rounded() && self.gradient.is_some() {
+                // The rounded_rect pipeline only takes a color, tessellate the corners instead
+                let outline = self.outline();
+
+                let mut vertices = Vec::with_capacity(outline.len() + 1);
+                vertices.push(self.center());
+                vertices.extend(outline.iter());
+
+                fill_paint(renderer, &mut self.buffer, &mut self.gradient_buffer, &vertices, &tessellate::fan(outline.len()), self.color, &self.gradient)?;
+            } else if self.is_rounded() {
+                renderer.rounded_rect(&mut self.buffer, self.position, self.wh, self.radius, self.color)?;
+            } else {
+                let vertices = vec![
+                    self.position,
+                    self.position + Vector::new(self.wh.x(), 0f32),
+                    self.position + Vector::new(0f32, self.wh.y()),
+                    self.position + self.wh,
+                ];
+
+                fill_paint(renderer, &mut self.buffer, &mut self.gradient_buffer, &vertices, &[0u32, 1u32, 2u32, 1u32, 3u32, 2u32], self.color, &self.gradient)?;
             }
         }
-        // let vertex_buffer = CpuAccessibleBuffer::from_iter(
-        //     device.clone(), BufferUsage::all(), false, 
-        //     vec![   self.position,
-        //         self.position + Vector::new(self.wh.x(), 0f32),
-        //         self.position + Vector::new(0f32, self.wh.y()),
-        //         self.position + self.wh
-        //     ].iter().cloned()
-        // ).unwrap();
-
-        // let index_buffer = CpuAccessibleBuffer::from_iter(
-        //     device.clone(), BufferUsage::all(), false, 
-        //     vec![0u16, 1u16, 2u16, 1u16, 3u16, 2u16].iter().cloned()
-        // ).unwrap();
-
-        // let col = self.color.to_float();
-
-        // builder.draw_indexed(
-        //     pipelines.default_fill.clone(), 
-        //     dynamic_state, 
-        //     vec![vertex_buffer.clone()], 
-        //     index_buffer.clone(),
-        //     (),
-        //     vs::ty::PushConstantData {
-        //         resolution: [resolution.x(), resolution.y()],
-        //         r: col[0],
-        //         g: col[1],
-        //         b: col[2],
-        //         a: col[3],
-        //     }
-        // ).unwrap();
+
+        let outline = self.outline();
+        draw_stroke(renderer, &mut self.stroke_buffer, &outline, true, self.format)
     }
 }
 
@@ -191,9 +279,12 @@ pub struct Triangle {
     pub c: Vector,
 
     pub color: Color,
+    pub gradient: Option<Gradient>,
     pub format: ShapeFormat,
 
     pub buffer: Option<Buffer>,
+    pub gradient_buffer: Option<TexturedBuffer<Vector>>,
+    pub stroke_buffer: Option<Buffer>,
 }
 
 impl Triangle {
@@ -204,9 +295,12 @@ impl Triangle {
             c,
 
             color: Color::default(),
+            gradient: None,
             format: ShapeFormat::Fill,
 
             buffer: None,
+            gradient_buffer: None,
+            stroke_buffer: None,
         }
     }
 }
@@ -222,57 +316,33 @@ impl Shape for Triangle {
         ) / 2f32).abs()
     }
 
+    fn outline(&self) -> Vec<Vector> {
+        vec![self.a, self.b, self.c]
+    }
+
     fn color(&mut self, c: Color) {
         self.color = c;
     }
+    fn gradient(&mut self, g: Gradient) {
+        self.gradient = Some(g);
+        self.gradient_buffer = None;
+    }
     fn format(&mut self, f: ShapeFormat) {
         self.format = f;
+        self.stroke_buffer = None;
     }
 
     fn contains(&self, v: Vector) -> bool {
-        self.area() == Triangle::new(v, self.b, self.c).area() + Triangle::new(self.a, v, self.c).area() + Triangle::new(self.a, self.b, v).area()
-    }
-
-    fn draw(&mut self,
-        device: Arc<Device>,
-        builder: &mut AutoCommandBufferBuilder,
-        pipelines: &ShapesPipeline,
-        dynamic_state: &DynamicState,
-        resolution: Vector,
-    ) {
-        match &self.buffer {
-            Some(b) => {
-                let col = self.color.to_float();
-
-                builder.draw_indexed(
-                    pipelines.default_fill.clone(), 
-                    dynamic_state, 
-                    vec![b.vertex_buffer.clone()],
-                    b.index_buffer.clone(),
-                    (),
-                    vs::ty::PushConstantData {
-                        resolution: [resolution.x(), resolution.y()],
-                        r: col[0],
-                        g: col[1],
-                        b: col[2],
-                        a: col[3],
-                    },
-                ).unwrap();
-            },
-            None => {
-                let vertex_buffer = CpuAccessibleBuffer::from_iter(
-                    device.clone(), BufferUsage::all(), false, 
-                    vec![self.a, self.b, self.c].iter().cloned()
-                ).unwrap();
-        
-                let index_buffer = CpuAccessibleBuffer::from_iter(
-                    device.clone(), BufferUsage::all(), false, 
-                    vec![0u16, 1u16, 2u16].iter().cloned()
-                ).unwrap();
-        
-                self.buffer = Some(Buffer::new(vertex_buffer, index_buffer));
-            }
+        tessellate::triangle_contains(v, self.a, self.b, self.c)
+    }
+
+    fn draw(&mut self, renderer: &mut dyn Renderer) -> Result<()> {
+        if self.format.fills() {
+            fill_paint(renderer, &mut self.buffer, &mut self.gradient_buffer, &[self.a, self.b, self.c], &[0u32, 1u32, 2u32], self.color, &self.gradient)?;
         }
+
+        let outline = self.outline();
+        draw_stroke(renderer, &mut self.stroke_buffer, &outline, true, self.format)
     }
 }
 
@@ -305,3 +375,644 @@ macro_rules! tri {
     };
 }
 
+
+/// Ellipse is also used for circles, where both radii are the same.
+#[derive(Debug, Clone)]
+pub struct Ellipse {
+    pub position: Vector,
+    pub radius: Vector,
+
+    pub color: Color,
+    pub gradient: Option<Gradient>,
+    pub format: ShapeFormat,
+
+    // Norm of the transform the cached buffers were tessellated for
+    pub scale: f32,
+
+    pub buffer: Option<Buffer>,
+    pub gradient_buffer: Option<TexturedBuffer<Vector>>,
+    pub stroke_buffer: Option<Buffer>,
+}
+
+impl Ellipse {
+    pub fn new(position: Vector, radius: Vector) -> Ellipse {
+        Ellipse {
+            position,
+            radius,
+
+            color: Color::default(),
+            gradient: None,
+            format: ShapeFormat::Fill,
+
+            scale: 1f32,
+
+            buffer: None,
+            gradient_buffer: None,
+            stroke_buffer: None,
+        }
+    }
+
+    pub fn circle(position: Vector, radius: f32) -> Ellipse {
+        Ellipse::new(position, Vector::new(radius, radius))
+    }
+}
+
+impl Shape for Ellipse {
+    fn center(&self) -> Vector {
+        self.position
+    }
+    fn area(&self) -> f32 {
+        PI * self.radius.x() * self.radius.y()
+    }
+
+    fn outline(&self) -> Vec<Vector> {
+        tessellate::ellipse(self.position, self.radius, self.scale)
+    }
+
+    fn color(&mut self, c: Color) {
+        self.color = c;
+    }
+    fn gradient(&mut self, g: Gradient) {
+        self.gradient = Some(g);
+        self.gradient_buffer = None;
+    }
+    fn format(&mut self, f: ShapeFormat) {
+        self.format = f;
+        self.stroke_buffer = None;
+    }
+
+    fn contains(&self, v: Vector) -> bool {
+        let d = (v - self.position) / self.radius;
+
+        d.dot(d) <= 1f32
+    }
+
+    fn draw(&mut self, renderer: &mut dyn Renderer) -> Result<()> {
+        // Tessellate again when the on-screen size calls for a different number of segments
+        let scale = renderer.scale();
+
+        if tessellate::ellipse_segments(self.radius, scale) != tessellate::ellipse_segments(self.radius, self.scale) {
+            self.scale = scale;
+            self.buffer = None;
+            self.gradient_buffer = None;
+            self.stroke_buffer = None;
+        }
+
+        let outline = self.outline();
+
+        if self.format.fills() {
+            let mut vertices = Vec::with_capacity(outline.len() + 1);
+            vertices.push(self.position);
+            vertices.extend(outline.iter());
+
+            fill_paint(renderer, &mut self.buffer, &mut self.gradient_buffer, &vertices, &tessellate::fan(outline.len()), self.color, &self.gradient)?;
+        }
+
+        draw_stroke(renderer, &mut self.stroke_buffer, &outline, true, self.format)
+    }
+}
+
+// Average of the points, the origin when there are none
+fn mean(points: &[Vector]) -> Vector {
+    if points.is_empty() {
+        return Vector::default();
+    }
+
+    points.iter().fold(Vector::default(), |acc, &p| acc + p) / points.len() as f32
+}
+
+/// Polygon is a closed, possibly concave, shape through a list of points.
+#[derive(Debug, Clone)]
+pub struct Polygon {
+    pub points: Vec<Vector>,
+
+    pub color: Color,
+    pub gradient: Option<Gradient>,
+    pub format: ShapeFormat,
+
+    pub buffer: Option<Buffer>,
+    pub gradient_buffer: Option<TexturedBuffer<Vector>>,
+    pub stroke_buffer: Option<Buffer>,
+}
+
+impl Polygon {
+    pub fn new(points: Vec<Vector>) -> Polygon {
+        Polygon {
+            points,
+
+            color: Color::default(),
+            gradient: None,
+            format: ShapeFormat::Fill,
+
+            buffer: None,
+            gradient_buffer: None,
+            stroke_buffer: None,
+        }
+    }
+}
+
+impl Shape for Polygon {
+    fn center(&self) -> Vector {
+        mean(&self.points)
+    }
+    fn area(&self) -> f32 {
+        tessellate::signed_area(&self.points).abs() / 2f32
+    }
+
+    fn outline(&self) -> Vec<Vector> {
+        self.points.clone()
+    }
+
+    fn color(&mut self, c: Color) {
+        self.color = c;
+    }
+    fn gradient(&mut self, g: Gradient) {
+        self.gradient = Some(g);
+        self.gradient_buffer = None;
+    }
+    fn format(&mut self, f: ShapeFormat) {
+        self.format = f;
+        self.stroke_buffer = None;
+    }
+
+    fn contains(&self, v: Vector) -> bool {
+        tessellate::polygon_contains(&self.points, v)
+    }
+
+    fn draw(&mut self, renderer: &mut dyn Renderer) -> Result<()> {
+        if self.format.fills() {
+            fill_paint(renderer, &mut self.buffer, &mut self.gradient_buffer, &self.points, &tessellate::triangulate(&self.points), self.color, &self.gradient)?;
+        }
+
+        draw_stroke(renderer, &mut self.stroke_buffer, &self.points, true, self.format)
+    }
+}
+
+/// Polyline is an open line through a list of points, it is only ever stroked.
+/// The line is drawn with color, a format only sets its width, join and cap.
+#[derive(Debug, Clone)]
+pub struct Polyline {
+    pub points: Vec<Vector>,
+
+    pub color: Color,
+    pub format: ShapeFormat,
+
+    pub buffer: Option<Buffer>,
+}
+
+impl Polyline {
+    pub fn new(points: Vec<Vector>) -> Polyline {
+        let color = Color::new(0, 0, 0, u8::MAX);
+
+        Polyline {
+            points,
+
+            color,
+            format: ShapeFormat::Line(Stroke::new(1f32, color)),
+
+            buffer: None,
+        }
+    }
+
+    fn stroke(&self) -> Stroke {
+        match self.format.stroke() {
+            Some(s) => Stroke { color: self.color, ..s },
+            None => Stroke::new(1f32, self.color),
+        }
+    }
+}
+
+impl Shape for Polyline {
+    fn center(&self) -> Vector {
+        mean(&self.points)
+    }
+    fn area(&self) -> f32 {
+        0f32
+    }
+
+    fn outline(&self) -> Vec<Vector> {
+        self.points.clone()
+    }
+
+    fn color(&mut self, c: Color) {
+        self.color = c;
+    }
+    fn gradient(&mut self, _g: Gradient) {}
+    // The stroke color of the format becomes the color of the line
+    fn format(&mut self, f: ShapeFormat) {
+        if let Some(s) = f.stroke() {
+            self.color = s.color;
+        }
+
+        self.format = f;
+        self.buffer = None;
+    }
+
+    fn contains(&self, v: Vector) -> bool {
+        let hw = self.stroke().width / 2f32;
+
+        self.points
+            .windows(2)
+            .any(|s| tessellate::segment_distance(v, s[0], s[1]) <= hw)
+    }
+
+    fn draw(&mut self, renderer: &mut dyn Renderer) -> Result<()> {
+        let s = self.stroke();
+        let mesh = tessellate::stroke(&self.points, false, s.width, s.join, s.cap);
+
+        renderer.fill(&mut self.buffer, &mesh.vertices, &mesh.indices, s.color)
+    }
+}
+
+/// Path is a shape described by SVG path data, flattened into one or more contours.
+/// Overlapping contours are filled with the even-odd rule, so inner contours cut holes.
+#[derive(Debug, Clone)]
+pub struct Path {
+    pub contours: Vec<Contour>,
+
+    pub color: Color,
+    pub gradient: Option<Gradient>,
+    pub format: ShapeFormat,
+
+    pub buffer: Option<Buffer>,
+    pub gradient_buffer: Option<TexturedBuffer<Vector>>,
+    pub stroke_buffer: Option<Buffer>,
+}
+
+impl Path {
+    pub fn new(contours: Vec<Contour>) -> Path {
+        Path {
+            contours,
+
+            color: Color::default(),
+            gradient: None,
+            format: ShapeFormat::Fill,
+
+            buffer: None,
+            gradient_buffer: None,
+            stroke_buffer: None,
+        }
+    }
+
+    // Parses path data, scaling it and then moving it to position
+    pub fn parse(d: &str, position: Vector, scale: f32) -> Result<Path> {
+        let mut contours = path::parse(d)?;
+
+        if contours.is_empty() {
+            return Err(CoreError::InvalidPathData);
+        }
+
+        for c in contours.iter_mut() {
+            for p in c.points.iter_mut() {
+                *p = *p * scale + position;
+            }
+        }
+
+        Ok(Path::new(contours))
+    }
+
+    fn points(&self) -> Vec<Vec<Vector>> {
+        self.contours.iter().map(|c| c.points.clone()).collect()
+    }
+}
+
+impl Shape for Path {
+    fn center(&self) -> Vector {
+        let points: Vec<Vector> = self.contours.iter().flat_map(|c| c.points.iter().cloned()).collect();
+
+        if points.is_empty() {
+            return Vector::default();
+        }
+
+        let (min, max) = tessellate::bounds(&points);
+
+        (min + max) / 2f32
+    }
+    fn area(&self) -> f32 {
+        // Holes are wound against their outer contour in well formed paths, so they subtract
+        self.contours
+            .iter()
+            .map(|c| tessellate::signed_area(&c.points))
+            .sum::<f32>()
+            .abs() / 2f32
+    }
+
+    // The first contour, nothing for a path built without any
+    fn outline(&self) -> Vec<Vector> {
+        self.contours.first().map(|c| c.points.clone()).unwrap_or_default()
+    }
+
+    fn color(&mut self, c: Color) {
+        self.color = c;
+    }
+    fn gradient(&mut self, g: Gradient) {
+        self.gradient = Some(g);
+        self.gradient_buffer = None;
+    }
+    fn format(&mut self, f: ShapeFormat) {
+        self.format = f;
+        self.stroke_buffer = None;
+    }
+
+    fn contains(&self, v: Vector) -> bool {
+        tessellate::contours_contain(&self.points(), v)
+    }
+
+    fn draw(&mut self, renderer: &mut dyn Renderer) -> Result<()> {
+        if self.format.fills() {
+            let mesh = tessellate::triangulate_contours(&self.points());
+            fill_paint(renderer, &mut self.buffer, &mut self.gradient_buffer, &mesh.vertices, &mesh.indices, self.color, &self.gradient)?;
+        }
+
+        match self.format.stroke() {
+            Some(s) => {
+                let mut mesh = Mesh::new();
+
+                for c in self.contours.iter() {
+                    mesh.append(tessellate::stroke(&c.points, c.closed, s.width, s.join, s.cap));
+                }
+
+                renderer.fill(&mut self.stroke_buffer, &mesh.vertices, &mesh.indices, s.color)
+            },
+            None => Ok(()),
+        }
+    }
+}
+
+/// Text is a block of laid out text, drawn from its glyph atlas.
+/// It is always filled, an outline format is ignored.
+#[derive(Debug, Clone)]
+pub struct Text {
+    pub layout: TextLayout,
+
+    pub color: Color,
+    pub format: ShapeFormat,
+
+    pub buffer: Option<TexturedBuffer>,
+}
+
+impl Text {
+    pub fn new(layout: TextLayout) -> Text {
+        Text {
+            layout,
+
+            color: Color::default(),
+            format: ShapeFormat::Fill,
+
+            buffer: None,
+        }
+    }
+}
+
+impl Shape for Text {
+    fn center(&self) -> Vector {
+        self.layout.position + self.layout.size / 2f32
+    }
+    fn area(&self) -> f32 {
+        self.layout.size.x() * self.layout.size.y()
+    }
+
+    fn outline(&self) -> Vec<Vector> {
+        let (min, max) = (self.layout.position, self.layout.position + self.layout.size);
+
+        vec![min, Vector::new(max.x(), min.y()), max, Vector::new(min.x(), max.y())]
+    }
+
+    fn color(&mut self, c: Color) {
+        self.color = c;
+    }
+    fn gradient(&mut self, _g: Gradient) {}
+    fn format(&mut self, f: ShapeFormat) {
+        self.format = f;
+    }
+
+    fn contains(&self, v: Vector) -> bool {
+        let (min, max) = (self.layout.position, self.layout.position + self.layout.size);
+
+        v.x() >= min.x() && v.x() <= max.x() && v.y() >= min.y() && v.y() <= max.y()
+    }
+
+    fn draw(&mut self, renderer: &mut dyn Renderer) -> Result<()> {
+        renderer.textured(
+            &mut self.buffer,
+            &self.layout.vertices,
+            &self.layout.indices,
+            &self.layout.atlas,
+            false,
+            self.color,
+        )
+    }
+}
+
+/// ImageMode decides how an Image is scaled into its rectangle.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ImageMode {
+    // Scaled to fit inside, keeping the aspect ratio, centered
+    Fit,
+    // Scaled to cover all of it, keeping the aspect ratio, cropping the overflow
+    Fill,
+    // Scaled to exactly its size
+    Stretch,
+    // Repeated at its original size from the top left
+    Tile,
+}
+
+/// Image is a picture drawn into a rectangle.
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub position: Vector,
+    pub wh: Vector,
+
+    // Premultiplied pixels
+    pub image: Frame,
+    pub mode: ImageMode,
+    pub opacity: f32,
+
+    pub format: ShapeFormat,
+
+    pub buffer: Option<TexturedBuffer>,
+}
+
+impl Image {
+    // image is expected with straight alpha, as loaded by Frame::load
+    pub fn new(position: Vector, wh: Vector, mut image: Frame, mode: ImageMode) -> Image {
+        image.premultiply();
+
+        Image {
+            position,
+            wh,
+
+            image,
+            mode,
+            opacity: 1f32,
+
+            format: ShapeFormat::Fill,
+
+            buffer: None,
+        }
+    }
+
+    // Quad covering the drawn part of the rectangle, with texture coordinates
+    fn quad(&self) -> [TexturedVertex; 4] {
+        let image_wh = Vector::new(self.image.width as f32, self.image.height as f32);
+
+        let (min, max, uv_min, uv_max) = match self.mode {
+            ImageMode::Stretch => (self.position, self.position + self.wh, Vector::new(0f32, 0f32), Vector::new(1f32, 1f32)),
+            ImageMode::Fit => {
+                let scale = (self.wh.x() / image_wh.x()).min(self.wh.y() / image_wh.y());
+                let size = image_wh * scale;
+                let min = self.position + (self.wh - size) / 2f32;
+
+                (min, min + size, Vector::new(0f32, 0f32), Vector::new(1f32, 1f32))
+            },
+            ImageMode::Fill => {
+                let scale = (self.wh.x() / image_wh.x()).max(self.wh.y() / image_wh.y());
+                let visible = self.wh / (image_wh * scale);
+                let uv_min = (Vector::new(1f32, 1f32) - visible) / 2f32;
+
+                (self.position, self.position + self.wh, uv_min, uv_min + visible)
+            },
+            ImageMode::Tile => (self.position, self.position + self.wh, Vector::new(0f32, 0f32), self.wh / image_wh),
+        };
+
+        // Vertices sit on pixel centers, move back half a pixel to line the quad up with the pixel edges
+        let (min, max) = (min - 0.5f32, max - 0.5f32);
+
+        [
+            TexturedVertex::new(min.position, uv_min.position),
+            TexturedVertex::new([max.x(), min.y()], [uv_max.x(), uv_min.y()]),
+            TexturedVertex::new([min.x(), max.y()], [uv_min.x(), uv_max.y()]),
+            TexturedVertex::new(max.position, uv_max.position),
+        ]
+    }
+}
+
+impl Shape for Image {
+    fn center(&self) -> Vector {
+        self.position + self.wh / 2f32
+    }
+    fn area(&self) -> f32 {
+        self.wh.x() * self.wh.y()
+    }
+
+    fn outline(&self) -> Vec<Vector> {
+        vec![
+            self.position,
+            self.position + Vector::new(self.wh.x(), 0f32),
+            self.position + self.wh,
+            self.position + Vector::new(0f32, self.wh.y()),
+        ]
+    }
+
+    // Images keep their own colors
+    fn color(&mut self, _c: Color) {}
+    fn gradient(&mut self, _g: Gradient) {}
+    fn format(&mut self, f: ShapeFormat) {
+        self.format = f;
+    }
+
+    fn contains(&self, v: Vector) -> bool {
+        v.x() >= self.position.x() && v.x() <= self.position.x() + self.wh.x()
+            && v.y() >= self.position.y() && v.y() <= self.position.y() + self.wh.y()
+    }
+
+    fn draw(&mut self, renderer: &mut dyn Renderer) -> Result<()> {
+        let alpha = (self.opacity.max(0f32).min(1f32) * u8::MAX as f32).round() as u8;
+        let quad = self.quad();
+
+        renderer.textured(
+            &mut self.buffer,
+            &quad,
+            &[0u32, 1u32, 2u32, 1u32, 3u32, 2u32],
+            &self.image,
+            self.mode == ImageMode::Tile,
+            Color::new(u8::MAX, u8::MAX, u8::MAX, alpha),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::software::SoftwareRenderer;
+    use crate::transform::Transform;
+
+    #[test]
+    fn triangle_contains_interior_points() {
+        let t = Triangle::new(Vector::new(0.1f32, 0.3f32), Vector::new(97.3f32, 12.7f32), Vector::new(31.9f32, 88.1f32));
+        let reversed = Triangle::new(t.c, t.b, t.a);
+
+        // Every point of a grid, against the barycentric coordinates of the point
+        for y in 0..100 {
+            for x in 0..100 {
+                let v = Vector::new(x as f32 + 0.37f32, y as f32 + 0.61f32);
+
+                let d = (t.b - t.a).cross(t.c - t.a);
+                let u = (v - t.a).cross(t.c - t.a) / d;
+                let w = (t.b - t.a).cross(v - t.a) / d;
+                let inside = u >= 0f32 && w >= 0f32 && u + w <= 1f32;
+
+                assert_eq!(t.contains(v), inside, "{:?}", v);
+                assert_eq!(reversed.contains(v), inside, "{:?}", v);
+            }
+        }
+
+        assert!(t.contains(t.a) && t.contains(t.b) && t.contains(t.c));
+        assert!(t.contains(t.center()));
+    }
+
+    #[test]
+    fn empty_points_have_a_center() {
+        assert_eq!(Polygon::new(Vec::new()).center(), Vector::default());
+        assert_eq!(Polyline::new(Vec::new()).center(), Vector::default());
+        assert_eq!(Path::new(Vec::new()).center(), Vector::default());
+        assert!(Path::new(Vec::new()).outline().is_empty());
+        assert_eq!(Polygon::new(vec![Vector::new(0f32, 0f32), Vector::new(4f32, 0f32), Vector::new(2f32, 6f32)]).center(), Vector::new(2f32, 2f32));
+    }
+
+    #[test]
+    fn ellipse_segments_follow_the_transform() {
+        let mut e = Ellipse::circle(Vector::new(10f32, 10f32), 8f32);
+        let mut renderer = SoftwareRenderer::new(160, 160);
+        let small = e.outline().len();
+
+        renderer.transform(Transform::rotate(0.5f32) * Transform::scale(Vector::new(1f32, 8f32)));
+        e.draw(&mut renderer).unwrap();
+
+        assert!((e.scale - 8f32).abs() < 1e-4);
+        assert!(e.outline().len() > small);
+        assert_eq!(e.outline().len(), tessellate::ellipse_segments(e.radius, 8f32));
+    }
+
+    #[test]
+    fn polylines_stroke_with_their_color() {
+        let mut p = Polyline::new(vec![Vector::new(0f32, 0f32), Vector::new(10f32, 0f32)]);
+        let black = Color::new(0, 0, 0, u8::MAX);
+        let red = Color::new(255, 0, 0, u8::MAX);
+
+        assert_eq!(p.stroke().color, black);
+
+        p.format(ShapeFormat::Line(Stroke::new(3f32, red)));
+        assert_eq!(p.stroke().color, red);
+        assert_eq!(p.stroke().width, 3f32);
+
+        // Hovering recolors the line through color
+        p.color(black);
+        assert_eq!(p.stroke().color, black);
+    }
+    #[test]
+    fn stretched_images_do_not_wrap_at_their_edges() {
+        // Red on the left and blue on the right, stretched over 40 by 10 pixels
+        let frame = Frame::new(2, 1, vec![255, 0, 0, 255, 0, 0, 255, 255]);
+        let mut image = Image::new(Vector::new(0f32, 0f32), Vector::new(40f32, 10f32), frame, ImageMode::Stretch);
+        let mut renderer = SoftwareRenderer::new(40, 10);
+
+        image.draw(&mut renderer).unwrap();
+
+        let first = renderer.pixels[5 * 40];
+        let last = renderer.pixels[5 * 40 + 39];
+
+        assert!(first[0] > 0.99f32 && first[2] < 0.01f32);
+        assert!(last[2] > 0.99f32 && last[0] < 0.01f32);
+    }
+}
@@ -0,0 +1,233 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::decoder;
+use crate::frame::Frame;
+use crate::headless::HeadlessState;
+use crate::error::Result;
+
+/// The fixtures directory of this crate, so snapshots find it from any working directory.
+pub const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures");
+
+/// Comparison is the result of diffing a rendered Frame against its reference image.
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    // Number of pixels where any channel differs by more than the tolerance
+    pub mismatched: usize,
+
+    // Largest per channel difference found
+    pub max_delta: u8,
+
+    // Visualisation of the differences, mismatched pixels are drawn in red
+    pub diff: Frame,
+}
+
+impl Comparison {
+    pub fn passed(&self) -> bool {
+        self.mismatched == 0
+    }
+}
+
+/// Compares two frames per pixel, a pixel mismatches when any channel differs by more than tolerance.
+/// Frames with different dimensions never match.
+pub fn compare(actual: &Frame, expected: &Frame, tolerance: u8) -> Comparison {
+    let width = actual.width.max(expected.width);
+    let height = actual.height.max(expected.height);
+
+    let mut diff = Frame::new(width, height, vec![0u8; (width * height * 4) as usize]);
+    let mut mismatched = 0;
+    let mut max_delta = 0u8;
+
+    for y in 0..height {
+        for x in 0..width {
+            let i = ((y * width + x) * 4) as usize;
+
+            if x >= actual.width || y >= actual.height || x >= expected.width || y >= expected.height {
+                mismatched += 1;
+                max_delta = u8::MAX;
+                diff.data[i..i + 4].copy_from_slice(&[255, 0, 0, 255]);
+                continue;
+            }
+
+            let a = actual.pixel(x, y);
+            let e = expected.pixel(x, y);
+
+            let delta = a.iter()
+                .zip(e.iter())
+                .map(|(a, e)| (*a as i16 - *e as i16).abs() as u8)
+                .max()
+                .unwrap();
+
+            max_delta = max_delta.max(delta);
+
+            if delta > tolerance {
+                mismatched += 1;
+                diff.data[i..i + 4].copy_from_slice(&[255, 0, 0, 255]);
+            } else {
+                // Faded grayscale of the expected image so mismatches stand out
+                let l = ((e[0] as u16 + e[1] as u16 + e[2] as u16) / 3 / 4) as u8;
+                diff.data[i..i + 4].copy_from_slice(&[l, l, l, 255]);
+            }
+        }
+    }
+
+    Comparison {
+        mismatched,
+        max_delta,
+        diff,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SnapshotStatus {
+    Passed,
+    Failed(Comparison),
+    // No reference image existed, one is written when blessing
+    Missing,
+}
+
+#[derive(Debug, Clone)]
+pub struct SnapshotResult {
+    pub config: PathBuf,
+    pub status: SnapshotStatus,
+}
+
+/// Snapshots renders every widget TOML under a fixtures directory and compares it to
+/// the png with the same name next to it.
+pub struct Snapshots {
+    pub root: PathBuf,
+
+    // Allowed per channel difference, GPUs rasterize edges slightly differently
+    pub tolerance: u8,
+
+    // Overwrite the reference images with the current output instead of comparing
+    pub bless: bool,
+}
+
+impl Snapshots {
+    pub fn new<P: AsRef<Path>>(root: P) -> Snapshots {
+        Snapshots {
+            root: root.as_ref().to_path_buf(),
+            tolerance: 2,
+            bless: false,
+        }
+    }
+
+    pub fn reference_path(config: &Path) -> PathBuf {
+        config.with_extension("png")
+    }
+
+    pub fn diff_path(config: &Path) -> PathBuf {
+        config.with_extension("diff.png")
+    }
+
+    pub fn actual_path(config: &Path) -> PathBuf {
+        config.with_extension("actual.png")
+    }
+
+    pub fn run(&self, headless: &HeadlessState) -> Result<Vec<SnapshotResult>> {
+        let pattern = self.root.join("**/*.toml");
+
+        let mut results = Vec::new();
+
+        for path in glob::glob(pattern.to_str().unwrap())? {
+            let config = path?;
+            let status = self.check(headless, &config)?;
+
+            results.push(SnapshotResult {
+                config,
+                status,
+            });
+        }
+
+        Ok(results)
+    }
+
+    pub fn check(&self, headless: &HeadlessState, config: &Path) -> Result<SnapshotStatus> {
+        let widget_config = decoder::decode(config.to_str().unwrap())?;
+        let actual = headless.render(widget_config)?;
+
+        let reference = Snapshots::reference_path(config);
+
+        if self.bless {
+            actual.save_png(&reference)?;
+            return Ok(SnapshotStatus::Passed);
+        }
+
+        if !reference.exists() {
+            return Ok(SnapshotStatus::Missing);
+        }
+
+        let expected = Frame::load_png(&reference)?;
+        let comparison = compare(&actual, &expected, self.tolerance);
+
+        if comparison.passed() {
+            // Clean up the output of an earlier failed run
+            for stale in [Snapshots::diff_path(config), Snapshots::actual_path(config)].iter() {
+                if stale.exists() {
+                    fs::remove_file(stale)?;
+                }
+            }
+
+            return Ok(SnapshotStatus::Passed);
+        }
+
+        comparison.diff.save_png(Snapshots::diff_path(config))?;
+        actual.save_png(Snapshots::actual_path(config))?;
+
+        Ok(SnapshotStatus::Failed(comparison))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, px: [u8; 4]) -> Frame {
+        Frame::new(width, height, px.iter().cycle().take((width * height * 4) as usize).cloned().collect())
+    }
+
+    #[test]
+    fn identical_frames_pass() {
+        let frame = solid(4, 3, [10, 20, 30, 255]);
+        let comparison = compare(&frame, &frame.clone(), 0);
+
+        assert!(comparison.passed());
+        assert_eq!(comparison.max_delta, 0);
+    }
+
+    #[test]
+    fn differences_within_tolerance_pass() {
+        let actual = solid(4, 3, [10, 20, 30, 255]);
+        let expected = solid(4, 3, [12, 20, 29, 255]);
+        let comparison = compare(&actual, &expected, 2);
+
+        assert!(comparison.passed());
+        assert_eq!(comparison.max_delta, 2);
+    }
+
+    #[test]
+    fn differences_over_tolerance_fail() {
+        let actual = solid(4, 3, [10, 20, 30, 255]);
+        let mut expected = actual.clone();
+        expected.data[4..8].copy_from_slice(&[10, 20, 33, 255]);
+
+        let comparison = compare(&actual, &expected, 2);
+
+        assert!(!comparison.passed());
+        assert_eq!(comparison.mismatched, 1);
+        assert_eq!(comparison.max_delta, 3);
+        assert_eq!(comparison.diff.pixel(1, 0), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn different_sizes_fail() {
+        let actual = solid(4, 3, [10, 20, 30, 255]);
+        let expected = solid(4, 4, [10, 20, 30, 255]);
+        let comparison = compare(&actual, &expected, 2);
+
+        assert!(!comparison.passed());
+        assert_eq!(comparison.mismatched, 4);
+        assert_eq!((comparison.diff.width, comparison.diff.height), (4, 4));
+    }
+}
@@ -0,0 +1,392 @@
+use crate::render::{Renderer, BlendMode, rounded_rect_quad};
+use crate::buffer::{Buffer, BatchBuffer, ColoredVertex, TexturedBuffer, TexturedVertex};
+use crate::vector::Vector;
+use crate::transform::Transform;
+use crate::color::{self, Color};
+use crate::frame::Frame;
+use crate::gradient::{Gradient, GradientSpan};
+use crate::shape::rounded_rect_distance;
+use crate::error::Result;
+
+/// SoftwareRenderer is a pure CPU rasterizer, used when no Vulkan device is available.
+///
+/// Pixels are stored as premultiplied floats in linear light, like the sRGB attachments of
+/// VulkanRenderer hold them, and blended with the BlendMode set, "over" by default.
+#[derive(Debug, Clone)]
+pub struct SoftwareRenderer {
+    pub width: u32,
+    pub height: u32,
+
+    pub pixels: Vec<[f32; 4]>,
+
+    // Set by transform, applied to everything drawn
+    pub transform: Transform,
+
+    // Takes pixels back into the space of the geometry, None when nothing can be drawn
+    inverse: Option<Transform>,
+
+    // Set by blend, applied to everything drawn
+    pub blend: BlendMode,
+
+    // One flag per pixel for every pushed clip, the last one is inside all of them
+    clips: Vec<Vec<bool>>,
+}
+
+impl SoftwareRenderer {
+    pub fn new(width: u32, height: u32) -> SoftwareRenderer {
+        SoftwareRenderer {
+            width,
+            height,
+            pixels: vec![[0f32; 4]; (width * height) as usize],
+            transform: Transform::identity(),
+            inverse: Some(Transform::identity()),
+            blend: BlendMode::Normal,
+            clips: Vec::new(),
+        }
+    }
+
+    // Blends a premultiplied color onto the pixel at x, y, unless a clip leaves it out
+    pub fn blend_pixel(&mut self, x: u32, y: u32, src: [f32; 4]) {
+        let i = (y * self.width + x) as usize;
+
+        if !self.clips.last().map(|c| c[i]).unwrap_or(true) {
+            return;
+        }
+
+        self.pixels[i] = self.blend.apply(src, self.pixels[i]);
+    }
+
+    // Calls shade for every pixel covered by the transformed triangle, with the weights
+    // of a, b and c at the pixel center. The closure returns the premultiplied color to blend.
+    pub fn rasterize<F>(&mut self, a: Vector, b: Vector, c: Vector, mut shade: F)
+        where F: FnMut(Vector, [f32; 3]) -> [f32; 4]
+    {
+        for (x, y, p, w) in self.covered(a, b, c) {
+            let color = shade(p, w);
+            self.blend_pixel(x, y, color);
+        }
+    }
+
+    // The pixels covered by the transformed triangle, with the pixel center in the space of
+    // the geometry and the weights of a, b and c there
+    fn covered(&self, a: Vector, b: Vector, c: Vector) -> Vec<(u32, u32, Vector, [f32; 3])> {
+        let mut pixels = Vec::new();
+
+        let inverse = match self.inverse {
+            Some(i) => i,
+            None => return pixels,
+        };
+
+        // Match the half pixel offset applied by the vertex shader
+        let t = self.transform;
+        let (pa, pb, pc) = (t.apply(a) + 0.5f32, t.apply(b) + 0.5f32, t.apply(c) + 0.5f32);
+
+        let area = (pb - pa).cross(pc - pa);
+        if area == 0f32 {
+            return pixels;
+        }
+
+        // Wind every triangle the same way so inside is always positive
+        let flipped = area < 0f32;
+        let (pb, pc) = if flipped { (pc, pb) } else { (pb, pc) };
+        let area = area.abs();
+
+        let min_x = pa.x().min(pb.x()).min(pc.x()).floor().max(0f32) as u32;
+        let min_y = pa.y().min(pb.y()).min(pc.y()).floor().max(0f32) as u32;
+        let max_x = (pa.x().max(pb.x()).max(pc.x()).ceil().max(0f32) as u32).min(self.width);
+        let max_y = (pa.y().max(pb.y()).max(pc.y()).ceil().max(0f32) as u32).min(self.height);
+
+        // Each edge is opposite the corner its weight belongs to
+        let edges = [(pb, pc), (pc, pa), (pa, pb)];
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let p = Vector::new(x as f32 + 0.5f32, y as f32 + 0.5f32);
+
+                let mut w = [0f32; 3];
+                let mut inside = true;
+
+                for (i, &(v0, v1)) in edges.iter().enumerate() {
+                    w[i] = (v1 - v0).cross(p - v0) / area;
+
+                    // Top-left rule so pixels on shared edges are only drawn once
+                    inside &= w[i] > 0f32 || (w[i] == 0f32 && is_top_left(v1 - v0));
+                }
+
+                if !inside {
+                    continue;
+                }
+
+                if flipped {
+                    w.swap(1, 2);
+                }
+
+                // The pixel center in the space of the geometry, where the shaders evaluate it
+                pixels.push((x, y, inverse.apply(Vector::new(x as f32, y as f32)), w));
+            }
+        }
+
+        pixels
+    }
+
+    pub fn triangle(&mut self, a: Vector, b: Vector, c: Vector, color: [f32; 4]) {
+        self.rasterize(a, b, c, |_, _| color);
+    }
+
+    pub fn textured_triangle(&mut self, a: TexturedVertex, b: TexturedVertex, c: TexturedVertex, image: &Frame, repeat: bool, color: [f32; 4]) {
+        let corner = |v: TexturedVertex| Vector::new(v.position[0], v.position[1]);
+
+        self.rasterize(corner(a), corner(b), corner(c), |_, w| {
+            let u = a.uv[0] * w[0] + b.uv[0] * w[1] + c.uv[0] * w[2];
+            let v = a.uv[1] * w[0] + b.uv[1] * w[1] + c.uv[1] * w[2];
+
+            let texel = sample(image, u, v, repeat);
+
+            [texel[0] * color[0], texel[1] * color[1], texel[2] * color[2], texel[3] * color[3]]
+        });
+    }
+
+    // Encodes every channel the way an sRGB attachment stores it, with straight alpha like png expects
+    pub fn to_frame(&self) -> Frame {
+        let mut frame = self.to_premultiplied_frame();
+        frame.unpremultiply();
+
+        frame
+    }
+
+    // Same as to_frame, but keeps the premultiplied colors a swapchain image holds
+    pub fn to_premultiplied_frame(&self) -> Frame {
+        let data = self.pixels
+            .iter()
+            .flat_map(|px| {
+                let (rgb, a) = (&px[..3], px[3]);
+
+                rgb.iter()
+                    .map(|&c| color::linear_to_srgb(c))
+                    .chain(Some(a.max(0f32).min(1f32)))
+                    .map(|c| (c * u8::MAX as f32).round() as u8)
+                    .collect::<Vec<u8>>()
+            })
+            .collect();
+
+        Frame::new(self.width, self.height, data)
+    }
+}
+
+fn is_top_left(edge: Vector) -> bool {
+    (edge.y() == 0f32 && edge.x() > 0f32) || edge.y() < 0f32
+}
+
+// Bilinear sample with repeat or clamp to edge addressing, the same as the samplers used by VulkanRenderer,
+// then taken into linear light like the shaders do
+fn sample(image: &Frame, u: f32, v: f32, repeat: bool) -> [f32; 4] {
+    let x = u * image.width as f32 - 0.5f32;
+    let y = v * image.height as f32 - 0.5f32;
+
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+
+    let texel = |tx: i64, ty: i64| -> [f32; 4] {
+        let (tx, ty) = if repeat {
+            (tx.rem_euclid(image.width as i64), ty.rem_euclid(image.height as i64))
+        } else {
+            (tx.max(0).min(image.width as i64 - 1), ty.max(0).min(image.height as i64 - 1))
+        };
+
+        let px = image.pixel(tx as u32, ty as u32);
+
+        [
+            px[0] as f32 / u8::MAX as f32,
+            px[1] as f32 / u8::MAX as f32,
+            px[2] as f32 / u8::MAX as f32,
+            px[3] as f32 / u8::MAX as f32,
+        ]
+    };
+
+    let (x0, y0) = (x0 as i64, y0 as i64);
+    let (t00, t10, t01, t11) = (texel(x0, y0), texel(x0 + 1, y0), texel(x0, y0 + 1), texel(x0 + 1, y0 + 1));
+
+    let mut out = [0f32; 4];
+    for (i, o) in out.iter_mut().enumerate() {
+        let top = t00[i] + (t10[i] - t00[i]) * fx;
+        let bottom = t01[i] + (t11[i] - t01[i]) * fx;
+
+        *o = top + (bottom - top) * fy;
+    }
+
+    color::decode_pixel(out)
+}
+
+impl Renderer for SoftwareRenderer {
+    fn resolution(&self) -> Vector {
+        Vector::new(self.width as f32, self.height as f32)
+    }
+
+    fn begin(&mut self, clear: Color) -> Result<()> {
+        let col = clear.to_premultiplied_linear();
+
+        for px in self.pixels.iter_mut() {
+            *px = col;
+        }
+
+        self.blend = BlendMode::Normal;
+
+        Ok(())
+    }
+
+    fn transform(&mut self, transform: Transform) {
+        self.transform = transform;
+        self.inverse = transform.inverse();
+    }
+
+    fn scale(&self) -> f32 {
+        self.transform.norm()
+    }
+
+    fn blend(&mut self, mode: BlendMode) {
+        self.blend = mode;
+    }
+
+    fn fill(&mut self,
+        _buffer: &mut Option<Buffer>,
+        vertices: &[Vector],
+        indices: &[u32],
+        color: Color,
+    ) -> Result<()> {
+        let col = color.to_premultiplied_linear();
+
+        for i in indices.chunks(3) {
+            if i.len() == 3 {
+                self.triangle(vertices[i[0] as usize], vertices[i[1] as usize], vertices[i[2] as usize], col);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn rounded_rect(&mut self,
+        _buffer: &mut Option<Buffer>,
+        position: Vector,
+        size: Vector,
+        radius: [f32; 4],
+        color: Color,
+    ) -> Result<()> {
+        let col = color.to_premultiplied_linear();
+        let half = size / 2f32;
+        let quad = rounded_rect_quad(position, size);
+
+        // The same quad and distance field as the rounded_rect shaders
+        let mut shade = |p: Vector, _| {
+            let coverage = (0.5f32 - rounded_rect_distance(p + 0.5f32 - position - half, half, radius)).max(0f32).min(1f32);
+
+            [col[0] * coverage, col[1] * coverage, col[2] * coverage, col[3] * coverage]
+        };
+
+        self.rasterize(quad[0], quad[1], quad[2], &mut shade);
+        self.rasterize(quad[1], quad[3], quad[2], &mut shade);
+
+        Ok(())
+    }
+
+    fn textured(&mut self,
+        _buffer: &mut Option<TexturedBuffer>,
+        vertices: &[TexturedVertex],
+        indices: &[u32],
+        image: &Frame,
+        repeat: bool,
+        color: Color,
+    ) -> Result<()> {
+        let col = color.to_premultiplied_linear();
+
+        for i in indices.chunks(3) {
+            if i.len() == 3 {
+                self.textured_triangle(vertices[i[0] as usize], vertices[i[1] as usize], vertices[i[2] as usize], image, repeat, col);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn gradient(&mut self,
+        _buffer: &mut Option<TexturedBuffer<Vector>>,
+        vertices: &[Vector],
+        indices: &[u32],
+        gradient: &Gradient,
+        span: GradientSpan,
+    ) -> Result<()> {
+        let ramp = gradient.ramp();
+
+        for i in indices.chunks(3) {
+            if i.len() == 3 {
+                self.rasterize(vertices[i[0] as usize], vertices[i[1] as usize], vertices[i[2] as usize], |p, _| {
+                    sample(&ramp, span.t(p), 0.5f32, false)
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn batch(&mut self,
+        _buffer: &mut Option<BatchBuffer>,
+        vertices: &[ColoredVertex],
+        indices: &[u32],
+    ) -> Result<()> {
+        let corner = |v: ColoredVertex| Vector::new(v.position[0], v.position[1]);
+
+        for i in indices.chunks(3) {
+            if i.len() == 3 {
+                let (a, b, c) = (vertices[i[0] as usize], vertices[i[1] as usize], vertices[i[2] as usize]);
+
+                // Interpolated like the varying between the vertex and fragment shader
+                self.rasterize(corner(a), corner(b), corner(c), |_, w| {
+                    let mut color = [0f32; 4];
+                    for (k, out) in color.iter_mut().enumerate() {
+                        *out = a.color[k] * w[0] + b.color[k] * w[1] + c.color[k] * w[2];
+                    }
+
+                    color
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn push_clip(&mut self,
+        _buffer: &mut Option<Buffer>,
+        vertices: &[Vector],
+        indices: &[u32],
+    ) -> Result<()> {
+        let mut clip = vec![false; self.pixels.len()];
+
+        for i in indices.chunks(3) {
+            if i.len() == 3 {
+                for (x, y, _, _) in self.covered(vertices[i[0] as usize], vertices[i[1] as usize], vertices[i[2] as usize]) {
+                    clip[(y * self.width + x) as usize] = true;
+                }
+            }
+        }
+
+        // Nested clips only keep what the outer ones keep as well
+        if let Some(outer) = self.clips.last() {
+            for (c, &o) in clip.iter_mut().zip(outer.iter()) {
+                *c &= o;
+            }
+        }
+
+        self.clips.push(clip);
+
+        Ok(())
+    }
+
+    fn pop_clip(&mut self) -> Result<()> {
+        self.clips.pop();
+
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,655 @@
+use std::f32::consts::PI;
+
+use crate::vector::Vector;
+
+// Maximum distance in pixels between a curve and the straight segments approximating it
+pub const TOLERANCE: f32 = 0.25;
+
+// Miter joins longer than this many times the half line width are drawn beveled
+pub const MITER_LIMIT: f32 = 4.0;
+
+/// Mesh is an indexed triangle list, the form every shape is reduced to before drawing.
+#[derive(Debug, Clone, Default)]
+pub struct Mesh {
+    pub vertices: Vec<Vector>,
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    pub fn new() -> Mesh {
+        Mesh {
+            vertices: Vec::new(),
+            indices: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, v: Vector) -> u32 {
+        self.vertices.push(v);
+        (self.vertices.len() - 1) as u32
+    }
+
+    pub fn triangle(&mut self, a: u32, b: u32, c: u32) {
+        self.indices.extend_from_slice(&[a, b, c]);
+    }
+
+    // Adds a fan of triangles around center over the given points
+    pub fn fan(&mut self, center: u32, points: &[Vector]) {
+        let first = self.vertices.len() as u32;
+
+        for &p in points {
+            self.push(p);
+        }
+
+        for i in 1..points.len() as u32 {
+            self.triangle(center, first + i - 1, first + i);
+        }
+    }
+
+    // Adds all triangles of another mesh
+    pub fn append(&mut self, other: Mesh) {
+        let first = self.vertices.len() as u32;
+
+        self.vertices.extend(other.vertices);
+        self.indices.extend(other.indices.iter().map(|i| first + i));
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum LineJoin {
+    Miter,
+    Round,
+    Bevel,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum LineCap {
+    Butt,
+    Round,
+    Square,
+}
+
+/// Returns how many segments are needed to approximate an arc of the given radius
+/// and angle (in radians) within TOLERANCE, so larger curves get more vertices.
+pub fn arc_segments(radius: f32, angle: f32) -> usize {
+    if radius <= TOLERANCE {
+        return 1;
+    }
+
+    let step = 2f32 * (1f32 - TOLERANCE / radius).acos();
+    let segments = (angle.abs() / step).ceil() as usize;
+
+    segments.max(1).min(256)
+}
+
+/// How many segments ellipse uses for the given radius, drawn with a transform of the given norm.
+pub fn ellipse_segments(radius: Vector, scale: f32) -> usize {
+    arc_segments(radius.x().max(radius.y()) * scale, 2f32 * PI).max(12)
+}
+
+/// Points along an ellipse, starting at angle 0 and going clockwise on screen,
+/// fine enough to stay smooth when drawn with a transform of the given norm.
+pub fn ellipse(center: Vector, radius: Vector, scale: f32) -> Vec<Vector> {
+    let segments = ellipse_segments(radius, scale);
+
+    (0..segments)
+        .map(|i| {
+            let (sin, cos) = (i as f32 / segments as f32 * 2f32 * PI).sin_cos();
+            center + Vector::new(cos * radius.x(), sin * radius.y())
+        })
+        .collect()
+}
+
+/// Index buffer for a triangle fan around vertex 0 over the following n vertices.
+pub fn fan(n: usize) -> Vec<u32> {
+    let mut indices = Vec::with_capacity(n * 3);
+
+    for i in 0..n {
+        indices.push(0u32);
+        indices.push((i + 1) as u32);
+        indices.push(((i + 1) % n + 1) as u32);
+    }
+
+    indices
+}
+
+
+/// Points along an arc around center, starting at center + from and turning by angle radians.
+/// Both end points are included.
+pub fn arc(center: Vector, from: Vector, angle: f32) -> Vec<Vector> {
+    let segments = arc_segments(from.hypot(), angle);
+
+    (0..=segments)
+        .map(|i| center + from.rotated(angle * i as f32 / segments as f32))
+        .collect()
+}
+
+/// Outline of a rectangle with rounded corners, going clockwise on screen from the top left.
+/// radius is top left, top right, bottom right, bottom left.
+pub fn rounded_rect(position: Vector, size: Vector, radius: [f32; 4]) -> Vec<Vector> {
+    let corners = [
+        (position + Vector::new(radius[0], radius[0]), radius[0], PI),
+        (position + Vector::new(size.x() - radius[1], radius[1]), radius[1], 1.5f32 * PI),
+        (position + size - radius[2], radius[2], 0f32),
+        (position + Vector::new(radius[3], size.y() - radius[3]), radius[3], 0.5f32 * PI),
+    ];
+
+    let mut points = Vec::new();
+
+    for &(center, r, start) in corners.iter() {
+        if r <= 0f32 {
+            points.push(center);
+            continue;
+        }
+
+        let (sin, cos) = start.sin_cos();
+        points.extend(arc(center, Vector::new(cos * r, sin * r), 0.5f32 * PI));
+    }
+
+    points
+}
+
+fn normal(d: Vector) -> Vector {
+    Vector::new(-d.y(), d.x())
+}
+
+/// Turns a line through points into triangles covering it with the given width.
+/// Closed lines join their last point back to the first, open lines get caps on both ends.
+pub fn stroke(points: &[Vector], closed: bool, width: f32, join: LineJoin, cap: LineCap) -> Mesh {
+    let mut mesh = Mesh::new();
+
+    // Repeated points have no direction to extrude along
+    let mut pts: Vec<Vector> = Vec::with_capacity(points.len());
+    for &p in points {
+        if pts.last().map_or(true, |&l: &Vector| (l - p).hypot() > 1e-4) {
+            pts.push(p);
+        }
+    }
+    if closed && pts.len() > 2 && (pts[0] - pts[pts.len() - 1]).hypot() <= 1e-4 {
+        pts.pop();
+    }
+
+    let n = pts.len();
+    if n < 2 || width <= 0f32 {
+        return mesh;
+    }
+
+    let hw = width / 2f32;
+    let segments = if closed { n } else { n - 1 };
+
+    let direction = |j: usize| (pts[(j + 1) % n] - pts[j]).unit();
+    let length = |j: usize| (pts[(j + 1) % n] - pts[j]).hypot();
+
+    // Left (+normal) and right (-normal) vertex at the start and end of every segment
+    let mut starts = vec![(0u32, 0u32); segments];
+    let mut ends = vec![(0u32, 0u32); segments];
+
+    for (i, &p) in pts.iter().enumerate() {
+        let incoming = if i > 0 { Some(i - 1) } else if closed { Some(n - 1) } else { None };
+        let outgoing = if i < segments { Some(i) } else { None };
+
+        match (incoming, outgoing) {
+            (Some(a), Some(b)) => {
+                let (d0, d1) = (direction(a), direction(b));
+                let (n0, n1) = (normal(d0), normal(d1));
+                let cross = d0.cross(d1);
+
+                if cross.abs() < 1e-6 && d0.dot(d1) > 0f32 {
+                    // Straight continuation
+                    let l = mesh.push(p + n0 * hw);
+                    let r = mesh.push(p - n0 * hw);
+
+                    ends[a] = (l, r);
+                    starts[b] = (l, r);
+                } else if cross.abs() < 1e-6 {
+                    // The line turns back on itself, butt both sides and round the tip if asked
+                    ends[a] = (mesh.push(p + n0 * hw), mesh.push(p - n0 * hw));
+                    starts[b] = (mesh.push(p + n1 * hw), mesh.push(p - n1 * hw));
+
+                    if join == LineJoin::Round {
+                        let center = mesh.push(p);
+                        mesh.fan(center, &arc(p, n0 * hw, -PI));
+                    }
+                } else {
+                    // Normals point to the inside of the turn when it is positive
+                    let inner_sign = if cross > 0f32 { 1f32 } else { -1f32 };
+
+                    let m = (n0 + n1).unit();
+                    let miter = hw / m.dot(n0).max(1e-6);
+
+                    // Keep the inner corner from shooting past short segments
+                    let shortest = length(a).min(length(b));
+                    let inner_length = miter.min((shortest * shortest + hw * hw).sqrt());
+
+                    let inner = mesh.push(p + m * inner_sign * inner_length);
+
+                    let from = n0 * -inner_sign * hw;
+                    let to = n1 * -inner_sign * hw;
+
+                    let outer = match join {
+                        LineJoin::Miter if miter <= MITER_LIMIT * hw => {
+                            vec![p + from, p + m * -inner_sign * miter, p + to]
+                        },
+                        LineJoin::Round => {
+                            let angle = from.dot(to).max(-hw * hw).min(hw * hw) / (hw * hw);
+                            arc(p, from, angle.acos() * inner_sign)
+                        },
+                        _ => vec![p + from, p + to],
+                    };
+
+                    let first = mesh.vertices.len() as u32;
+                    mesh.fan(inner, &outer);
+                    let last = mesh.vertices.len() as u32 - 1;
+
+                    if inner_sign > 0f32 {
+                        ends[a] = (inner, first);
+                        starts[b] = (inner, last);
+                    } else {
+                        ends[a] = (first, inner);
+                        starts[b] = (last, inner);
+                    }
+                }
+            },
+            (None, Some(b)) => {
+                let d = direction(b);
+                let nrm = normal(d) * hw;
+                let base = if cap == LineCap::Square { p - d * hw } else { p };
+
+                let l = mesh.push(base + nrm);
+                let r = mesh.push(base - nrm);
+
+                if cap == LineCap::Round {
+                    let center = mesh.push(p);
+                    mesh.fan(center, &arc(p, nrm, PI));
+                }
+
+                starts[b] = (l, r);
+            },
+            (Some(a), None) => {
+                let d = direction(a);
+                let nrm = normal(d) * hw;
+                let base = if cap == LineCap::Square { p + d * hw } else { p };
+
+                let l = mesh.push(base + nrm);
+                let r = mesh.push(base - nrm);
+
+                if cap == LineCap::Round {
+                    let center = mesh.push(p);
+                    mesh.fan(center, &arc(p, nrm * -1f32, PI));
+                }
+
+                ends[a] = (l, r);
+            },
+            (None, None) => (),
+        }
+    }
+
+    for j in 0..segments {
+        let (l0, r0) = starts[j];
+        let (l1, r1) = ends[j];
+
+        mesh.triangle(l0, r0, l1);
+        mesh.triangle(r0, r1, l1);
+    }
+
+    mesh
+}
+
+// Twice the signed area of a closed polygon, positive when it winds the same way as fan()
+pub fn signed_area(points: &[Vector]) -> f32 {
+    let n = points.len();
+
+    (0..n).map(|i| points[i].cross(points[(i + 1) % n])).sum()
+}
+
+fn in_triangle(p: Vector, a: Vector, b: Vector, c: Vector) -> bool {
+    (b - a).cross(p - a) >= 0f32 && (c - b).cross(p - b) >= 0f32 && (a - c).cross(p - c) >= 0f32
+}
+
+/// Whether p is inside the triangle a, b, c, whichever way it is wound.
+pub fn triangle_contains(p: Vector, a: Vector, b: Vector, c: Vector) -> bool {
+    if (b - a).cross(c - a) < 0f32 {
+        in_triangle(p, a, c, b)
+    } else {
+        in_triangle(p, a, b, c)
+    }
+}
+
+/// Triangulates a simple, possibly concave, polygon by ear clipping.
+/// Returns indices into points.
+pub fn triangulate(points: &[Vector]) -> Vec<u32> {
+    let mut indices = Vec::new();
+
+    if points.len() < 3 {
+        return indices;
+    }
+
+    // Walk the polygon in positive winding so convex corners have a positive cross product
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    if signed_area(points) < 0f32 {
+        remaining.reverse();
+    }
+
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let mut clipped = false;
+
+        for i in 0..n {
+            let (ia, ib, ic) = (remaining[(i + n - 1) % n], remaining[i], remaining[(i + 1) % n]);
+            let (a, b, c) = (points[ia], points[ib], points[ic]);
+
+            let cross = (b - a).cross(c - b);
+
+            // Collinear and repeated points add nothing, drop them without a triangle
+            if cross == 0f32 {
+                remaining.remove(i);
+                clipped = true;
+                break;
+            }
+
+            if cross < 0f32 {
+                continue;
+            }
+
+            let blocked = remaining.iter().any(|&j| {
+                j != ia && j != ib && j != ic
+                    && points[j] != a && points[j] != b && points[j] != c
+                    && in_triangle(points[j], a, b, c)
+            });
+
+            if !blocked {
+                indices.extend_from_slice(&[ia as u32, ib as u32, ic as u32]);
+                remaining.remove(i);
+                clipped = true;
+                break;
+            }
+        }
+
+        // Self intersecting polygons can run out of ears, clip anyway so this always ends
+        if !clipped {
+            indices.extend_from_slice(&[remaining[n - 1] as u32, remaining[0] as u32, remaining[1] as u32]);
+            remaining.remove(0);
+        }
+    }
+
+    indices.extend(remaining.iter().map(|&i| i as u32));
+
+    indices
+}
+
+/// Triangulates a shape made of several contours with the even-odd rule.
+/// Contours inside an odd number of others are holes, each hole is bridged into the
+/// contour around it so the result can be ear clipped as one polygon.
+pub fn triangulate_contours(contours: &[Vec<Vector>]) -> Mesh {
+    let contours: Vec<&Vec<Vector>> = contours.iter().filter(|c| c.len() >= 3).collect();
+
+    let depth: Vec<usize> = contours
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            contours
+                .iter()
+                .enumerate()
+                .filter(|&(j, other)| j != i && polygon_contains(other, c[0]))
+                .count()
+        })
+        .collect();
+
+    let mut mesh = Mesh::new();
+
+    for (i, contour) in contours.iter().enumerate() {
+        if depth[i] % 2 != 0 {
+            continue;
+        }
+
+        let mut outer = (*contour).clone();
+        if signed_area(&outer) < 0f32 {
+            outer.reverse();
+        }
+
+        // Holes directly inside this contour, wound the other way
+        let mut holes: Vec<Vec<Vector>> = contours
+            .iter()
+            .enumerate()
+            .filter(|&(j, hole)| depth[j] == depth[i] + 1 && polygon_contains(contour, hole[0]))
+            .map(|(_, hole)| {
+                let mut hole = (*hole).clone();
+                if signed_area(&hole) > 0f32 {
+                    hole.reverse();
+                }
+                hole
+            })
+            .collect();
+
+        // Bridge the rightmost holes first so later bridges can not cross earlier ones
+        holes.sort_by(|a, b| max_x(b).partial_cmp(&max_x(a)).unwrap());
+
+        for hole in holes.iter() {
+            outer = bridge(&outer, hole);
+        }
+
+        let indices = triangulate(&outer);
+
+        mesh.append(Mesh {
+            vertices: outer,
+            indices,
+        });
+    }
+
+    mesh
+}
+
+fn max_x(points: &[Vector]) -> f32 {
+    points.iter().fold(f32::MIN, |acc, p| acc.max(p.x()))
+}
+
+fn in_triangle_any(p: Vector, a: Vector, b: Vector, c: Vector) -> bool {
+    let (d0, d1, d2) = ((b - a).cross(p - a), (c - b).cross(p - b), (a - c).cross(p - c));
+
+    (d0 >= 0f32 && d1 >= 0f32 && d2 >= 0f32) || (d0 <= 0f32 && d1 <= 0f32 && d2 <= 0f32)
+}
+
+// Joins a hole into the outer polygon by cutting from the rightmost point of the hole to
+// a vertex of the outer polygon visible from it, walking around the hole and back.
+fn bridge(outer: &[Vector], hole: &[Vector]) -> Vec<Vector> {
+    let (m_index, m) = hole
+        .iter()
+        .cloned()
+        .enumerate()
+        .fold((0, hole[0]), |acc, (i, p)| if p.x() > acc.1.x() { (i, p) } else { acc });
+
+    let n = outer.len();
+
+    // Closest edge crossed by a ray from m to the right
+    let mut hit: Option<(Vector, usize)> = None;
+
+    for i in 0..n {
+        let (a, b) = (outer[i], outer[(i + 1) % n]);
+
+        if a.y() == b.y() || (a.y() > m.y()) == (b.y() > m.y()) {
+            continue;
+        }
+
+        let x = a.x() + (m.y() - a.y()) / (b.y() - a.y()) * (b.x() - a.x());
+
+        if x >= m.x() && hit.map_or(true, |(h, _)| x < h.x()) {
+            let candidate = if a.x() > b.x() { i } else { (i + 1) % n };
+            hit = Some((Vector::new(x, m.y()), candidate));
+        }
+    }
+
+    let p_index = match hit {
+        Some((intersection, candidate)) => {
+            // Another vertex inside the triangle between m, the hit and the candidate
+            // would block the view, the one closest in angle to the ray is visible.
+            let p = outer[candidate];
+
+            (0..n)
+                .filter(|&j| j != candidate && outer[j] != p && in_triangle_any(outer[j], m, intersection, p))
+                .min_by(|&a, &b| {
+                    let (da, db) = (outer[a] - m, outer[b] - m);
+                    let ta = da.y().abs().atan2(da.x());
+                    let tb = db.y().abs().atan2(db.x());
+
+                    ta.partial_cmp(&tb).unwrap().then(da.hypot().partial_cmp(&db.hypot()).unwrap())
+                })
+                .unwrap_or(candidate)
+        },
+        None => (0..n)
+            .min_by(|&a, &b| (outer[a] - m).hypot().partial_cmp(&(outer[b] - m).hypot()).unwrap())
+            .unwrap(),
+    };
+
+    let mut merged = Vec::with_capacity(n + hole.len() + 2);
+    merged.extend_from_slice(&outer[..=p_index]);
+    merged.extend_from_slice(&hole[m_index..]);
+    merged.extend_from_slice(&hole[..=m_index]);
+    merged.extend_from_slice(&outer[p_index..]);
+
+    merged
+}
+
+/// Even-odd point in polygon test.
+pub fn polygon_contains(points: &[Vector], p: Vector) -> bool {
+    let n = points.len();
+    let mut inside = false;
+
+    for i in 0..n {
+        let a = points[i];
+        let b = points[(i + n - 1) % n];
+
+        if (a.y() > p.y()) != (b.y() > p.y())
+            && p.x() < (b.x() - a.x()) * (p.y() - a.y()) / (b.y() - a.y()) + a.x()
+        {
+            inside = !inside;
+        }
+    }
+
+    inside
+}
+
+/// Even-odd point test over several contours, as used to fill paths.
+pub fn contours_contain(contours: &[Vec<Vector>], p: Vector) -> bool {
+    contours.iter().filter(|c| polygon_contains(c, p)).count() % 2 == 1
+}
+
+/// Smallest rectangle containing all points, as its top left and bottom right.
+pub fn bounds(points: &[Vector]) -> (Vector, Vector) {
+    points.iter().fold(
+        (Vector::new(f32::MAX, f32::MAX), Vector::new(f32::MIN, f32::MIN)),
+        |(min, max), p| (
+            Vector::new(min.x().min(p.x()), min.y().min(p.y())),
+            Vector::new(max.x().max(p.x()), max.y().max(p.y())),
+        ),
+    )
+}
+
+/// Distance from p to the line segment between a and b.
+pub fn segment_distance(p: Vector, a: Vector, b: Vector) -> f32 {
+    let ab = b - a;
+    let len = ab.dot(ab);
+
+    if len == 0f32 {
+        return (p - a).hypot();
+    }
+
+    let t = ((p - a).dot(ab) / len).max(0f32).min(1f32);
+
+    (p - (a + ab * t)).hypot()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(values: &[f32]) -> Vec<Vector> {
+        values.chunks(2).map(|c| Vector::new(c[0], c[1])).collect()
+    }
+
+    // An arrow pointing right, concave where the shaft meets the head
+    fn arrow() -> Vec<Vector> {
+        points(&[10., 40., 70., 40., 70., 10., 110., 60., 70., 110., 70., 80., 10., 80.])
+    }
+
+    fn triangle_area(points: &[Vector], i: &[u32]) -> f32 {
+        let (a, b, c) = (points[i[0] as usize], points[i[1] as usize], points[i[2] as usize]);
+
+        (b - a).cross(c - a).abs() / 2f32
+    }
+
+    #[test]
+    fn triangulates_concave_polygons() {
+        let u = points(&[0., 0., 30., 0., 30., 30., 20., 30., 20., 10., 10., 10., 10., 30., 0., 30.]);
+
+        for polygon in vec![arrow(), u] {
+            for p in vec![polygon.clone(), polygon.iter().rev().cloned().collect()] {
+                let indices = triangulate(&p);
+
+                assert_eq!(indices.len(), (p.len() - 2) * 3);
+
+                // The triangles cover the polygon exactly once and stay inside of it
+                let area: f32 = indices.chunks(3).map(|i| triangle_area(&p, i)).sum();
+                assert!((area - signed_area(&p).abs() / 2f32).abs() < 1e-3, "{} {:?}", area, indices);
+
+                for i in indices.chunks(3) {
+                    let center = (p[i[0] as usize] + p[i[1] as usize] + p[i[2] as usize]) / 3f32;
+                    assert!(polygon_contains(&p, center), "{:?} {:?}", i, center);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn skips_degenerate_polygons() {
+        assert!(triangulate(&points(&[0., 0., 10., 0.])).is_empty());
+
+        // The collinear point in the middle of the bottom edge adds no triangle
+        let square = points(&[0., 0., 5., 0., 10., 0., 10., 10., 0., 10.]);
+        let area: f32 = triangulate(&square).chunks(3).map(|i| triangle_area(&square, i)).sum();
+        assert_eq!(area, 100f32);
+    }
+
+    #[test]
+    fn contains_points_of_concave_polygons() {
+        let p = arrow();
+
+        assert!(polygon_contains(&p, Vector::new(20., 60.)));
+        assert!(polygon_contains(&p, Vector::new(100., 60.)));
+        assert!(polygon_contains(&p, Vector::new(75., 20.)));
+
+        // In the notches beside the shaft, and outside altogether
+        assert!(!polygon_contains(&p, Vector::new(40., 20.)));
+        assert!(!polygon_contains(&p, Vector::new(40., 95.)));
+        assert!(!polygon_contains(&p, Vector::new(120., 60.)));
+        assert!(!polygon_contains(&[], Vector::new(0., 0.)));
+    }
+
+    #[test]
+    fn even_odd_contours() {
+        let outer = points(&[0., 0., 30., 0., 30., 30., 0., 30.]);
+        let hole = points(&[10., 10., 20., 10., 20., 20., 10., 20.]);
+
+        assert!(contours_contain(&[outer.clone(), hole.clone()], Vector::new(5., 5.)));
+        assert!(!contours_contain(&[outer, hole], Vector::new(15., 15.)));
+    }
+
+    #[test]
+    fn indexes_meshes_past_u16() {
+        let mut mesh = Mesh::new();
+        let center = mesh.push(Vector::new(0., 0.));
+        let ring = ellipse(Vector::new(0., 0.), Vector::new(100., 100.), 1.);
+
+        while mesh.vertices.len() <= 70_000 {
+            mesh.fan(center, &ring);
+        }
+
+        let mut other = Mesh::new();
+        let first = other.push(Vector::new(0., 0.));
+        other.fan(first, &ring);
+        mesh.append(other);
+
+        let last = mesh.vertices.len() as u32 - 1;
+
+        assert!(mesh.indices.iter().all(|&i| i <= last));
+        assert_eq!(mesh.indices.iter().max(), Some(&last));
+    }
+}
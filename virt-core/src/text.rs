@@ -0,0 +1,279 @@
+use rusttype::{point, Font, GlyphId, Scale};
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::vector::Vector;
+use crate::frame::Frame;
+use crate::buffer::TexturedVertex;
+use crate::error::{CoreError, Result};
+
+// Glyphs are packed into rows no wider than this, unless one glyph alone is wider
+const ATLAS_WIDTH: u32 = 512;
+
+// Empty pixels around every glyph, so linear sampling never reads a neighbour
+const ATLAS_PADDING: u32 = 1;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right,
+}
+
+/// Loads a TrueType or OpenType font file.
+pub fn load_font<P: AsRef<Path>>(path: P) -> Result<Font<'static>> {
+    let data = fs::read(path)?;
+
+    Font::try_from_vec(data).ok_or(CoreError::InvalidFont)
+}
+
+/// TextLayout is laid out text, as textured quads into a glyph atlas.
+#[derive(Debug, Clone)]
+pub struct TextLayout {
+    // Premultiplied white glyphs, with the coverage in every channel
+    pub atlas: Frame,
+
+    pub vertices: Vec<TexturedVertex>,
+    pub indices: Vec<u32>,
+
+    // Top left and size of the laid out block
+    pub position: Vector,
+    pub size: Vector,
+}
+
+// A glyph placed at a whole pixel, so every use of a glyph rasterizes the same
+struct PlacedGlyph {
+    id: GlyphId,
+    x: f32,
+    baseline: f32,
+}
+
+/// Lays out text with its top left at position. Lines break at newlines and, when a
+/// wrap width is given, between words that would overflow it. Lines are aligned within
+/// the wrap width, or within the widest line when there is none.
+pub fn layout(
+    font: &Font,
+    text: &str,
+    position: Vector,
+    size: f32,
+    wrap: Option<f32>,
+    align: TextAlign,
+) -> TextLayout {
+    let scale = Scale::uniform(size);
+    let v_metrics = font.v_metrics(scale);
+    let line_height = (v_metrics.ascent - v_metrics.descent + v_metrics.line_gap).ceil();
+
+    let lines: Vec<String> = text
+        .lines()
+        .flat_map(|paragraph| wrap_line(font, scale, paragraph, wrap))
+        .collect();
+
+    let widths: Vec<f32> = lines.iter().map(|l| line_width(font, scale, l)).collect();
+    let width = wrap.unwrap_or_else(|| widths.iter().cloned().fold(0f32, f32::max));
+
+    let mut placed = Vec::new();
+
+    for (i, line) in lines.iter().enumerate() {
+        let offset = match align {
+            TextAlign::Left => 0f32,
+            TextAlign::Center => (width - widths[i]) / 2f32,
+            TextAlign::Right => width - widths[i],
+        };
+
+        let baseline = (position.y() + v_metrics.ascent + line_height * i as f32).round();
+        let mut x = position.x() + offset;
+        let mut previous = None;
+
+        for c in line.chars() {
+            let glyph = font.glyph(c).scaled(scale);
+            let id = glyph.id();
+
+            if let Some(p) = previous {
+                x += font.pair_kerning(scale, p, id);
+            }
+
+            placed.push(PlacedGlyph { id, x: x.round(), baseline });
+
+            x += glyph.h_metrics().advance_width;
+            previous = Some(id);
+        }
+    }
+
+    let (atlas, rects) = build_atlas(font, scale, &placed);
+
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+
+    for g in placed.iter() {
+        let bb = match font.glyph(g.id).scaled(scale).positioned(point(g.x, g.baseline)).pixel_bounding_box() {
+            Some(bb) => bb,
+            None => continue,
+        };
+
+        let (ax, ay) = rects[&g.id];
+        let (w, h) = (bb.width() as f32, bb.height() as f32);
+
+        let (u0, v0) = (ax as f32 / atlas.width as f32, ay as f32 / atlas.height as f32);
+        let (u1, v1) = ((ax as f32 + w) / atlas.width as f32, (ay as f32 + h) / atlas.height as f32);
+
+        // Vertices sit on pixel centers, so move back half a pixel to line the
+        // quad up with the pixel edges and sample every texel exactly once
+        let (x0, y0) = (bb.min.x as f32 - 0.5f32, bb.min.y as f32 - 0.5f32);
+        let (x1, y1) = (x0 + w, y0 + h);
+
+        let first = vertices.len() as u32;
+
+        vertices.push(TexturedVertex::new([x0, y0], [u0, v0]));
+        vertices.push(TexturedVertex::new([x1, y0], [u1, v0]));
+        vertices.push(TexturedVertex::new([x0, y1], [u0, v1]));
+        vertices.push(TexturedVertex::new([x1, y1], [u1, v1]));
+
+        indices.extend_from_slice(&[first, first + 1, first + 2, first + 1, first + 3, first + 2]);
+    }
+
+    TextLayout {
+        atlas,
+        vertices,
+        indices,
+        position,
+        size: Vector::new(width, line_height * lines.len() as f32),
+    }
+}
+
+fn line_width(font: &Font, scale: Scale, line: &str) -> f32 {
+    let mut width = 0f32;
+    let mut previous = None;
+
+    for c in line.chars() {
+        let glyph = font.glyph(c).scaled(scale);
+
+        if let Some(p) = previous {
+            width += font.pair_kerning(scale, p, glyph.id());
+        }
+
+        width += glyph.h_metrics().advance_width;
+        previous = Some(glyph.id());
+    }
+
+    width
+}
+
+// Greedy word wrap, a word longer than the wrap width gets a line of its own
+fn wrap_line(font: &Font, scale: Scale, paragraph: &str, wrap: Option<f32>) -> Vec<String> {
+    let wrap = match wrap {
+        Some(w) => w,
+        None => return vec![paragraph.to_string()],
+    };
+
+    let mut lines = Vec::new();
+    let mut line = String::new();
+
+    for word in paragraph.split_whitespace() {
+        let candidate = if line.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", line, word)
+        };
+
+        if !line.is_empty() && line_width(font, scale, &candidate) > wrap {
+            lines.push(line);
+            line = word.to_string();
+        } else {
+            line = candidate;
+        }
+    }
+
+    lines.push(line);
+
+    lines
+}
+
+// Rasterizes every distinct glyph once and packs them in rows.
+// Returns the atlas and the top left of each glyph in it.
+fn build_atlas(font: &Font, scale: Scale, placed: &[PlacedGlyph]) -> (Frame, HashMap<GlyphId, (u32, u32)>) {
+    let mut rects = HashMap::new();
+    let mut bitmaps = Vec::new();
+
+    // Widened for glyphs that do not fit a row, as at large font sizes
+    let width = placed.iter()
+        .filter_map(|g| font.glyph(g.id).scaled(scale).positioned(point(0f32, 0f32)).pixel_bounding_box())
+        .map(|bb| bb.width() as u32 + 2 * ATLAS_PADDING)
+        .fold(ATLAS_WIDTH, u32::max);
+
+    let (mut x, mut y, mut row) = (ATLAS_PADDING, ATLAS_PADDING, 0u32);
+
+    for g in placed.iter() {
+        if rects.contains_key(&g.id) {
+            continue;
+        }
+
+        let glyph = font.glyph(g.id).scaled(scale).positioned(point(0f32, 0f32));
+
+        let bb = match glyph.pixel_bounding_box() {
+            Some(bb) => bb,
+            None => continue,
+        };
+
+        let (w, h) = (bb.width() as u32, bb.height() as u32);
+
+        if x + w + ATLAS_PADDING > width && x > ATLAS_PADDING {
+            x = ATLAS_PADDING;
+            y += row + ATLAS_PADDING;
+            row = 0;
+        }
+
+        let mut coverage = vec![0u8; (w * h) as usize];
+        glyph.draw(|gx, gy, v| {
+            coverage[(gy * w + gx) as usize] = (v * u8::MAX as f32).round() as u8;
+        });
+
+        rects.insert(g.id, (x, y));
+        bitmaps.push((x, y, w, h, coverage));
+
+        x += w + ATLAS_PADDING;
+        row = row.max(h);
+    }
+
+    let height = (y + row + ATLAS_PADDING).max(1);
+
+    let mut data = vec![0u8; (width * height * 4) as usize];
+
+    for (bx, by, w, h, coverage) in bitmaps {
+        for gy in 0..h {
+            for gx in 0..w {
+                let c = coverage[(gy * w + gx) as usize];
+                let i = (((by + gy) * width + bx + gx) * 4) as usize;
+
+                data[i..i + 4].copy_from_slice(&[c, c, c, c]);
+            }
+        }
+    }
+
+    (Frame::new(width, height, data), rects)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lays_out_more_glyphs_than_u16_indexes() {
+        let font = load_font(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/fonts/DejaVuSans.ttf")).unwrap();
+        let text = "abcd ".repeat(4500);
+        let l = layout(&font, &text, Vector::new(0., 0.), 8., Some(400.), TextAlign::Left);
+
+        assert_eq!(l.vertices.len(), 4 * 18_000);
+        assert!(l.indices.iter().all(|&i| (i as usize) < l.vertices.len()));
+        assert_eq!(l.indices.last(), Some(&(l.vertices.len() as u32 - 2)));
+    }
+    #[test]
+    fn fits_glyphs_wider_than_the_atlas() {
+        let font = load_font(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/fonts/DejaVuSans.ttf")).unwrap();
+        let l = layout(&font, "W", Vector::new(0., 0.), 1200., None, TextAlign::Left);
+
+        assert!(l.atlas.width > ATLAS_WIDTH);
+        assert!(l.vertices.iter().all(|v| v.uv[0] <= 1f32 && v.uv[1] <= 1f32));
+    }
+}
@@ -1,5 +1,6 @@
 use crate::shape::Shape;
 use crate::vector::Vector;
+use crate::color::Color;
 use crate::action::{Action, ActionType};
 use crate::error::Result;
 
@@ -8,6 +9,12 @@ pub struct Button {
     pub shape: Box<dyn Shape>,
 
     pub action: Option<Action>,
+
+    pub color: Color,
+    // Replaces color while the cursor is over the button
+    pub hover_color: Option<Color>,
+
+    pub hovered: bool,
 }
 
 impl Button {
@@ -15,6 +22,37 @@ impl Button {
         Button {
             shape,
             action,
+            color: Color::default(),
+            hover_color: None,
+            hovered: false,
+        }
+    }
+
+    // Updates the hover state for the cursor at v, None when the cursor left the widget.
+    // Returns true when the button looks different because of it.
+    pub fn hover(&mut self, v: Option<Vector>) -> bool {
+        let hovered = self.hovered_at(v);
+
+        if hovered == self.hovered {
+            return false;
+        }
+
+        self.hovered = hovered;
+
+        match self.hover_color {
+            Some(c) => {
+                self.shape.color(if hovered { c } else { self.color });
+                true
+            },
+            None => false,
+        }
+    }
+
+    // Whether the cursor at v is over the button, without changing anything
+    pub fn hovered_at(&self, v: Option<Vector>) -> bool {
+        match v {
+            Some(v) => self.shape.contains(v),
+            None => false,
         }
     }
 
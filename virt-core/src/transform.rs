@@ -0,0 +1,115 @@
+use std::ops::Mul;
+
+use crate::vector::Vector;
+
+/// Transform is a 2D affine transform, a 2x2 linear part followed by a translation.
+/// It maps v to x * v.x + y * v.y + translation.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Transform {
+    // Columns of the linear part, where the x and y axes end up
+    pub x: Vector,
+    pub y: Vector,
+
+    pub translation: Vector,
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform::identity()
+    }
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform {
+            x: Vector::new(1f32, 0f32),
+            y: Vector::new(0f32, 1f32),
+            translation: Vector::default(),
+        }
+    }
+
+    pub fn translate(v: Vector) -> Transform {
+        Transform {
+            translation: v,
+            ..Transform::identity()
+        }
+    }
+
+    // Rotates clockwise on screen by angle in radians, the same direction as Vector::rotated
+    pub fn rotate(angle: f32) -> Transform {
+        Transform {
+            x: Vector::new(1f32, 0f32).rotated(angle),
+            y: Vector::new(0f32, 1f32).rotated(angle),
+            translation: Vector::default(),
+        }
+    }
+
+    pub fn scale(v: Vector) -> Transform {
+        Transform {
+            x: Vector::new(v.x(), 0f32),
+            y: Vector::new(0f32, v.y()),
+            translation: Vector::default(),
+        }
+    }
+
+    // Shears along x by the angle v.x and along y by the angle v.y, both in radians
+    pub fn skew(v: Vector) -> Transform {
+        Transform {
+            x: Vector::new(1f32, v.y().tan()),
+            y: Vector::new(v.x().tan(), 1f32),
+            translation: Vector::default(),
+        }
+    }
+
+    pub fn apply(&self, v: Vector) -> Vector {
+        self.x * v.x() + self.y * v.y() + self.translation
+    }
+
+    pub fn determinant(&self) -> f32 {
+        self.x.cross(self.y)
+    }
+
+    // None when the transform flattens everything onto a line or a point
+    pub fn inverse(&self) -> Option<Transform> {
+        let det = self.determinant();
+
+        if det == 0f32 || !det.is_finite() {
+            return None;
+        }
+
+        let x = Vector::new(self.y.y(), -self.x.y()) / det;
+        let y = Vector::new(-self.y.x(), self.x.x()) / det;
+
+        Some(Transform {
+            x,
+            y,
+            translation: Vector::default() - (x * self.translation.x() + y * self.translation.y()),
+        })
+    }
+
+    // The most the linear part stretches any length by, its largest singular value
+    pub fn norm(&self) -> f32 {
+        let (a, b, c) = (self.x.dot(self.x), self.x.dot(self.y), self.y.dot(self.y));
+        let d = ((a - c) / 2f32).hypot(b);
+
+        ((a + c) / 2f32 + d).sqrt()
+    }
+
+    // The linear part as the columns of a GLSL mat2
+    pub fn linear(&self) -> [[f32; 2]; 2] {
+        [self.x.position, self.y.position]
+    }
+}
+
+// a * b applies b first, then a
+impl Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, t: Transform) -> Transform {
+        Transform {
+            x: self.x * t.x.x() + self.y * t.x.y(),
+            y: self.x * t.y.x() + self.y * t.y.y(),
+            translation: self.apply(t.translation),
+        }
+    }
+}
@@ -1,8 +1,11 @@
 use vulkano::command_buffer::DynamicState;
+use vulkano::device::Device;
+use vulkano::format::Format;
 use vulkano::framebuffer::{Framebuffer, FramebufferAbstract, RenderPassAbstract};
-use vulkano::image::SwapchainImage;
+use vulkano::image::{AttachmentImage, ImageAccess, ImageViewAccess, SwapchainImage};
 use vulkano::instance::{Instance, PhysicalDevice, PhysicalDeviceType};
 use vulkano::pipeline::viewport::Viewport;
+use vulkano::swapchain::ColorSpace;
 use winit::window::Window;
 
 use std::sync::Arc;
@@ -13,15 +16,70 @@ use serde_derive::Deserialize;
 use std::fs;
 
 use crate::error::{CoreError, Result};
+use crate::render::Backend;
 
-pub fn find_device_index(instance: Arc<Instance>, ty: PhysicalDeviceType) -> Result<usize> {
-    for device in PhysicalDevice::enumerate(&instance) {
-        if device.ty() == ty {
-            return Ok(device.index())
-        }
+/// DeviceSelector pins the physical device from config.toml, either by its index
+/// or by a case insensitive part of its name, `device = 1` or `device = "llvmpipe"`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum DeviceSelector {
+    Index(usize),
+    Name(String),
+}
+
+// Lower is preferred, so software implementations like lavapipe are only used as a last resort
+fn device_rank(ty: PhysicalDeviceType) -> u8 {
+    match ty {
+        PhysicalDeviceType::DiscreteGpu => 0,
+        PhysicalDeviceType::IntegratedGpu => 1,
+        PhysicalDeviceType::VirtualGpu => 2,
+        PhysicalDeviceType::Cpu => 3,
+        PhysicalDeviceType::Other => 4,
+    }
+}
+
+/// Picks the physical device to render with. A selector pins a specific device,
+/// otherwise the highest ranked device with a graphics queue is chosen.
+pub fn find_device_index(instance: Arc<Instance>, selector: Option<&DeviceSelector>) -> Result<usize> {
+    let devices: Vec<PhysicalDevice> = PhysicalDevice::enumerate(&instance)
+        .filter(|d| d.queue_families().any(|q| q.supports_graphics()))
+        .collect();
+
+    let chosen = match selector {
+        Some(DeviceSelector::Index(i)) => {
+            devices.iter().find(|d| d.index() == *i)
+        },
+        Some(DeviceSelector::Name(name)) => {
+            let name = name.to_lowercase();
+            devices.iter().find(|d| d.name().to_lowercase().contains(&name))
+        },
+        None => {
+            devices.iter().min_by_key(|d| (device_rank(d.ty()), d.index()))
+        },
     };
 
-    Err(CoreError::NoSupportedPhysicalDevice)
+    match chosen {
+        Some(device) => {
+            let available: Vec<String> = devices
+                .iter()
+                .map(|d| format!("[{}] {} ({:?})", d.index(), d.name(), d.ty()))
+                .collect();
+
+            eprintln!(
+                "Using device: [{}] {} (type: {:?}), available: {}",
+                device.index(),
+                device.name(),
+                device.ty(),
+                available.join(", "),
+            );
+
+            Ok(device.index())
+        },
+        None => match selector {
+            Some(s) => Err(CoreError::PhysicalDeviceNotFound(s.clone())),
+            None => Err(CoreError::NoSupportedPhysicalDevice),
+        },
+    }
 }
 
 pub fn widget_paths(cfg: CoreConfig) -> Result<Paths> {
@@ -41,6 +99,15 @@ pub fn widget_paths(cfg: CoreConfig) -> Result<Paths> {
 #[derive(Debug, Clone, Deserialize)]
 pub struct CoreConfig {
     root_path: Option<PathBuf>,
+
+    // Physical device to use instead of the automatically ranked one <Optional>
+    pub device: Option<DeviceSelector>,
+
+    // MSAA sample count, 1, 2, 4 or 8, lowered to what the device supports <Optional>
+    pub msaa: Option<u32>,
+
+    // Vulkan (default) or Software, Vulkan falls back to Software without a Vulkan device <Optional>
+    pub backend: Option<Backend>,
 }
 
 impl CoreConfig {
@@ -54,11 +121,165 @@ impl CoreConfig {
     }
 }
 
+/// Picks the highest sample count the device can render color with, without going over requested.
+/// The stencil attachment is multisampled along with the color, and its formats all carry depth,
+/// so the count has to be supported for both of them as well.
+pub fn sample_count(physical: &PhysicalDevice, requested: u32) -> u32 {
+    let limits = physical.limits();
+    let supported = limits.framebuffer_color_sample_counts()
+        & limits.framebuffer_depth_sample_counts()
+        & limits.framebuffer_stencil_sample_counts();
+
+    let samples = [8u32, 4, 2, 1]
+        .iter()
+        .cloned()
+        .find(|&s| s <= requested && supported & s != 0)
+        .unwrap_or(1);
+
+    if samples != requested {
+        eprintln!("MSAA x{} is not supported by {}, using x{}", requested, physical.name(), samples);
+    }
+
+    samples
+}
+
+/// Picks the swapchain format the same way on every machine, an sRGB one so blending
+/// happens in linear light and the result is encoded for the display. The shaders write
+/// linear light, on any other format everything would come out too dark, so a surface
+/// without one is an error.
+pub fn surface_format(supported: &[(Format, ColorSpace)]) -> Result<(Format, ColorSpace)> {
+    let preferred = [Format::B8G8R8A8Srgb, Format::R8G8B8A8Srgb, Format::A8B8G8R8SrgbPack32];
+
+    preferred
+        .iter()
+        .find_map(|p| supported.iter().find(|&&(f, c)| f == *p && c == ColorSpace::SrgbNonLinear))
+        .cloned()
+        .ok_or(CoreError::NoSrgbSurfaceFormat)
+}
+
+/// Picks a depth stencil format the device can attach, the stencil holds the clips.
+/// Vulkan requires one of the first two to be supported.
+pub fn stencil_format(physical: &PhysicalDevice) -> Format {
+    [Format::D24Unorm_S8Uint, Format::D32Sfloat_S8Uint, Format::D16Unorm_S8Uint]
+        .iter()
+        .cloned()
+        .find(|f| f.properties(*physical).optimal_tiling_features.depth_stencil_attachment)
+        .unwrap_or(Format::D32Sfloat_S8Uint)
+}
+
+/// Creates the single pass render pass every widget is drawn with. With more than one sample
+/// the pass draws into a multisampled attachment and resolves it into the target image.
+/// The stencil attachment is cleared to 0 for every frame.
+pub fn create_render_pass(
+    device: Arc<Device>,
+    format: Format,
+    stencil: Format,
+    samples: u32,
+) -> Result<Arc<dyn RenderPassAbstract + Send + Sync>> {
+    if samples > 1 {
+        // The multisampled attachment comes first, so it takes the clear color
+        Ok(Arc::new(
+            vulkano::single_pass_renderpass!(
+                device,
+                attachments: {
+                    intermediary: {
+                        load: Clear,
+                        store: DontCare,
+                        format: format,
+                        samples: samples,
+                    },
+                    color: {
+                        load: DontCare,
+                        store: Store,
+                        format: format,
+                        samples: 1,
+                    },
+                    stencil: {
+                        load: Clear,
+                        store: DontCare,
+                        format: stencil,
+                        samples: samples,
+                    }
+                },
+                pass: {
+                    color: [intermediary],
+                    depth_stencil: {stencil},
+                    resolve: [color],
+                }
+            )?,
+        ))
+    } else {
+        Ok(Arc::new(
+            vulkano::single_pass_renderpass!(
+                device,
+                attachments: {
+                    color: {
+                        load: Clear,
+                        store: Store,
+                        format: format,
+                        samples: 1,
+                    },
+                    stencil: {
+                        load: Clear,
+                        store: DontCare,
+                        format: stencil,
+                        samples: 1,
+                    }
+                },
+                pass: {
+                    color: [color],
+                    depth_stencil: {stencil}
+                }
+            )?,
+        ))
+    }
+}
+
+/// Builds a framebuffer drawing into image, with a multisampled attachment in front of it
+/// when the render pass resolves, and a stencil attachment of the same size.
+pub fn create_framebuffer<I>(
+    device: Arc<Device>,
+    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    image: I,
+    stencil: Format,
+    samples: u32,
+) -> Result<Arc<dyn FramebufferAbstract + Send + Sync>>
+where
+    I: ImageAccess + ImageViewAccess + Clone + Send + Sync + 'static,
+{
+    let dimensions = image.dimensions().width_height();
+
+    if samples > 1 {
+        let intermediary = AttachmentImage::transient_multisampled(device.clone(), dimensions, samples, image.format())?;
+        let stencil = AttachmentImage::transient_multisampled(device, dimensions, samples, stencil)?;
+
+        Ok(Arc::new(
+            Framebuffer::start(render_pass)
+                .add(intermediary)?
+                .add(image)?
+                .add(stencil)?
+                .build()?,
+        ))
+    } else {
+        let stencil = AttachmentImage::transient(device, dimensions, stencil)?;
+
+        Ok(Arc::new(
+            Framebuffer::start(render_pass)
+                .add(image)?
+                .add(stencil)?
+                .build()?,
+        ))
+    }
+}
+
 pub fn window_size_dependent_setup(
+    device: Arc<Device>,
     images: &[Arc<SwapchainImage<Window>>],
     render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    stencil: Format,
+    samples: u32,
     dynamic_state: &mut DynamicState,
-) -> Vec<Arc<dyn FramebufferAbstract + Send + Sync>> {
+) -> Result<Vec<Arc<dyn FramebufferAbstract + Send + Sync>>> {
     let dimensions = images[0].dimensions();
 
     let viewport = Viewport {
@@ -71,36 +292,33 @@ pub fn window_size_dependent_setup(
     images
         .iter()
         .map(|image| {
-            Arc::new(
-                Framebuffer::start(render_pass.clone())
-                    .add(image.clone())
-                    .unwrap()
-                    .build()
-                    .unwrap(),
-            ) as Arc<dyn FramebufferAbstract + Send + Sync>
+            create_framebuffer(device.clone(), render_pass.clone(), image.clone(), stencil, samples)
         })
-        .collect::<Vec<_>>()
+        .collect()
 }
 
-#[derive(Debug)]
-pub enum Color {}
+/// Asks the compositor to blur the desktop behind the transparent parts of the window through
+/// the KDE blur hint. Compositors that do not know the hint, and anything but X11, ignore it.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn request_blur_behind(window: &Window) {
+    use std::ffi::CString;
+    use winit::platform::unix::WindowExtUnix;
+    use winit::platform::unix::x11::ffi;
 
-impl Color {
-    pub fn new(r: f32, g: f32, b: f32, a: f32) -> [f32; 4] {
-        [r, g, b, a]
-    }
+    let (xconn, xwindow) = match (window.xlib_xconnection(), window.xlib_window()) {
+        (Some(c), Some(w)) => (c, w),
+        _ => return,
+    };
 
-    pub fn none() -> [f32; 4] {
-        [0f32, 0f32, 0f32, 0f32]
-    }
+    let name = CString::new("_KDE_NET_WM_BLUR_BEHIND_REGION").unwrap();
 
-    pub fn from_hex(data: Vec<u8>) -> [f32; 4] {
-        let a = data[3] as f32 / u8::MAX as f32;
-        [
-            (data[0] as f32 / u8::MAX as f32) * a,
-            (data[1] as f32 / u8::MAX as f32) * a,
-            (data[2] as f32 / u8::MAX as f32) * a,
-            a,
-        ]
+    // An empty region stands for the whole window
+    unsafe {
+        let atom = (xconn.xlib.XInternAtom)(xconn.display, name.as_ptr(), ffi::False);
+        (xconn.xlib.XChangeProperty)(xconn.display, xwindow, atom, ffi::XA_CARDINAL, 32, ffi::PropModeReplace, std::ptr::null(), 0);
+        (xconn.xlib.XFlush)(xconn.display);
     }
 }
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+pub fn request_blur_behind(_window: &Window) {}
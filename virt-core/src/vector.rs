@@ -8,6 +8,8 @@ pub struct Vector {
     pub position: [f32; 2],
 }
 
+vulkano::impl_vertex!(Vector, position);
+
 impl Vector {
     // Create a new Vector
     pub fn new(x: f32, y: f32) -> Vector {
@@ -1,19 +1,27 @@
-use crate::decoder::{WidgetConfig, Shapes, Tools, Type};
+use rusttype::Font;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use crate::decoder::{WidgetConfig, ShapeWidgetConfig, ToolWidgetConfig, GroupConfig, MaskConfig, MaskModeConfig, Blend, TransformConfig, FormatConfig, Origin, Anchor, Shapes, Tools, Type, Radius, Format, Join, Cap, Align, Scaling, GradientConfig, GradientType, ShadowConfig};
+use crate::tessellate::{LineJoin, LineCap};
+use crate::text::{self, TextAlign};
+use crate::gradient::{Gradient, GradientKind};
+use crate::buffer::TexturedBuffer;
+use crate::effects::{Effects, Shadow};
 use crate::error::{CoreError, Result};
 use crate::shape::*;
 use crate::vector::Vector;
+use crate::transform::Transform;
 use crate::color::Color;
+use crate::frame::Frame;
 use crate::tools::Button;
 use crate::action::{Action, ActionType};
-use crate::pipelines::ShapesPipeline;
-
-use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
-use vulkano::buffer::CpuBufferPool;
-use vulkano::framebuffer::FramebufferAbstract;
-use vulkano::device::Device;
-use vulkano::format::ClearValue;
-
-use std::sync::Arc;
+use crate::render::{Renderer, BlendMode};
+use crate::batch::{Batch, Batcher};
+use crate::scene::{Scene, NodeId, NodeKind};
+use crate::clip::{Clip, Mask, MaskMode};
 
 #[derive(Debug)]
 pub struct Widget {
@@ -23,9 +31,24 @@ pub struct Widget {
 
     pub color: Color,
 
-    pub shapes: Vec<Box<dyn Shape>>,
+    // Drawn over the background color
+    pub gradient: Option<Gradient>,
+    pub gradient_buffer: Option<TexturedBuffer<Vector>>,
+
+    // Every shape and tool of the widget, drawn in tree order
+    pub scene: Scene,
+
+    // Solid fills packed into as few draw calls as the drawing order allows, kept between frames
+    pub batches: Vec<Batch>,
 
-    pub buttons: Vec<Button>,
+    // Set when the widget looks different from its last drawn frame
+    pub dirty: bool,
+
+    // When an animation needs its next frame, the widget turns dirty at that time
+    pub redraw_at: Option<Instant>,
+
+    // Whether the compositor is asked to blur what is behind the window
+    pub blur_behind: bool,
 }
 
 impl Widget {
@@ -34,161 +57,678 @@ impl Widget {
             bound: Vector::new(config.width, config.height),
             position: Vector::new(config.position[0], config.position[1]),
             color: Color::default(),
-            shapes: Vec::new(),
-            buttons: Vec::new(),
+            gradient: None,
+            gradient_buffer: None,
+            scene: Scene::new(),
+            batches: Vec::new(),
+            dirty: true,
+            redraw_at: None,
+            blur_behind: config.blur_behind.unwrap_or(false),
         };
 
         match config.color {
             Some(c) => {
-                widget.color = Color::from_hex(hex::decode(&c[1..])?);
+                widget.color = Color::parse(&c)?;
             },
             None => (),
         };
 
-        match config.shape {
-            Some(shapes) => {
-                for s in shapes {
-                    match s.shape_type {
-                        Shapes::Triangle => {
-                            if s.shape.len() != 6 {
-                                return Err(CoreError::InvalidShapeFormat);
-                            };
-
-                            let mut triangle = Triangle::new(
-                                Vector::new(s.shape[0], s.shape[1]),
-                                Vector::new(s.shape[2], s.shape[3]),
-                                Vector::new(s.shape[4], s.shape[5]),
-                            );
-
-                            match s.color {
-                                Some(c) => {
-                                    triangle.color(Color::from_hex(hex::decode(&c[1..])?));
-                                    widget.shapes.push(Box::new(triangle));
-                                },
-                                None => {
-                                    widget.shapes.push(Box::new(triangle));
-                                }
-                            }
-                        },
-                        Shapes::Rectangle => {
-                            if s.shape.len() != 4 {
-                                return Err(CoreError::InvalidShapeFormat);
-                            };
-
-                            let mut rectangle = Rectangle::new(
-                                s.shape[0], s.shape[1], s.shape[2], s.shape[3]
-                            );
-
-                            match s.color {
-                                Some(c) => {
-                                    rectangle.color(Color::from_hex(hex::decode(&c[1..])?));
-                                    widget.shapes.push(Box::new(rectangle));
-                                },
-                                None => {
-                                    widget.shapes.push(Box::new(rectangle));
-                                }
-                            };
-                        },
-                    };
-                };
-            },
+        match &config.gradient {
+            Some(g) => widget.gradient = Some(build_gradient(g)?),
             None => (),
         };
 
-        match config.tool {
-            Some(tools) => {
-                for t in tools {
-                    match t.ty {
-                        Tools::Button => {
-                            if t.shape.len() != 4 {
-                                return Err(CoreError::InvalidShapeFormat);
-                            };
-
-                            let mut rectangle = Rectangle::new(
-                                t.shape[0], t.shape[1], t.shape[2], t.shape[3]
-                            );
-
-                            match t.color {
-                                Some(c) => {
-                                    match t.action {
-                                         Some(a) => {
-                                             match a.ty {
-                                                 Type::Clicked => {
-                                                    rectangle.color(Color::from_hex(hex::decode(&c[1..])?));
-                                                    let button = Button::new(
-                                                        Box::new(rectangle),
-                                                        Some(Action::new(a.action, ActionType::Clicked)),
-                                                    );
-        
-                                                    widget.buttons.push(button);
-                                                 }
-                                             }
-                                         },
-                                         None => {
-                                            rectangle.color(Color::from_hex(hex::decode(&c[1..])?));
-                                            let button = Button::new(
-                                                Box::new(rectangle),
-                                                None,
-                                            );
-
-                                            widget.buttons.push(button);
-                                         }
-                                     }
-                                },
-                                None => {
-                                    match t.action {
-                                        Some(a) => {
-                                            match a.ty {
-                                                Type::Clicked => {
-                                                    let button = Button::new(
-                                                        Box::new(rectangle),
-                                                        Some(Action::new(a.action, ActionType::Clicked)),
-                                                    );
-         
-                                                    widget.buttons.push(button);
-                                                }
-                                            }
-                                        },
-                                        None => {
-                                           let button = Button::new(
-                                               Box::new(rectangle),
-                                               None,
-                                           );
-
-                                           widget.buttons.push(button);
-                                        }
-                                    }
-                                }
-                            };
-                        },
-                    };
-                };
-            },
+        let shadow = match &config.shadow {
+            Some(s) => Some(build_shadow(s)?),
+            None => None,
+        };
+
+        let opacity = config.opacity.unwrap_or(1f32).max(0f32).min(1f32);
+
+        // Backdrops blur what the scene drew behind them, so the background has to be part of it
+        if shadow.is_some() || opacity < 1f32 || uses_backdrop(&config.shape, &config.group) {
+            // The window itself stays transparent, the background becomes a panel inset far
+            // enough for its shadow to fit inside the window, and fades with everything else
+            let inset = match &shadow {
+                Some(s) => s.blur.ceil() + s.offset.x().abs().max(s.offset.y().abs()),
+                None => 0f32,
+            };
+
+            if inset * 2f32 >= config.width || inset * 2f32 >= config.height {
+                return Err(CoreError::WidgetTooSmall(inset));
+            }
+
+            let mut panel = Rectangle::new(inset, inset, config.width - inset * 2f32, config.height - inset * 2f32);
+            panel.color(widget.color);
+
+            match widget.gradient.take() {
+                Some(g) => panel.gradient(g),
+                None => (),
+            };
+
+            widget.color = Color::default();
+            let root = widget.scene.root;
+            let id = widget.scene.add(root, NodeKind::Shape(Box::new(Effects::new(Box::new(panel), shadow, 0f32))))?;
+
+            // The background stays beneath everything, whatever layer or z it is given
+            let node = widget.scene.get_mut(id).unwrap();
+            node.layer = Some(0);
+            node.z = i32::MIN;
+        }
+
+        let root = widget.scene.root;
+        widget.scene.get_mut(root).unwrap().opacity = opacity;
+
+        match &config.layers {
+            Some(l) => widget.scene.set_layers(l),
             None => (),
         };
 
+        // Fonts are loaded once, however many Text shapes use them
+        let mut fonts = HashMap::new();
+
+        let root = widget.scene.root;
+        build_children(&mut widget.scene, root, &config.shape, &config.tool, &config.group, widget.bound, &config.root, &mut fonts)?;
 
         Ok(widget)
     }
 
-    pub fn draw(
-        &mut self,
-        device: Arc<Device>,
-        builder: &mut AutoCommandBufferBuilder, 
-        frame_buffer: Arc<dyn FramebufferAbstract + Send + Sync>,
-        pipelines: &ShapesPipeline,
-        dynamic_state: &DynamicState,
-    ) -> Result<()> {
-        builder.begin_render_pass(frame_buffer.clone(), false, vec![ClearValue::Float(self.color.to_float())])?;
+    // Marks the widget to be drawn again, for changes the widget cannot see itself like new data
+    pub fn invalidate(&mut self) {
+        self.dirty = true;
+    }
+
+    // Requests a frame at when, keeping the earliest of all requests
+    pub fn request_frame(&mut self, when: Instant) {
+        self.redraw_at = Some(match self.redraw_at {
+            Some(t) => t.min(when),
+            None => when,
+        });
+    }
+
+    // Updates which buttons are under the cursor, None when it left the widget
+    pub fn hover(&mut self, v: Option<Vector>) {
+        // Hidden buttons are missing from the draw order, they are never hovered,
+        // and neither is whatever a clip cuts away of a button
+        let scene = &self.scene;
+        let locals: HashMap<NodeId, Vector> = scene.draw_order()
+            .into_iter()
+            .filter_map(|(id, t)| match (v, t.inverse()) {
+                (Some(v), Some(inverse)) if scene.unclipped(id, v) => Some((id, inverse.apply(v))),
+                _ => None,
+            })
+            .collect();
+
+        // Only the buttons whose hover state changes are touched, so the composites of the
+        // others stay as they are
+        let changed: Vec<NodeId> = self.scene.nodes()
+            .filter(|n| match &n.kind {
+                NodeKind::Button(button) => button.hovered_at(locals.get(&n.id).cloned()) != button.hovered,
+                _ => false,
+            })
+            .map(|n| n.id)
+            .collect();
 
-        for shape in self.shapes.iter_mut() {
-            shape.draw(device.clone(), builder, &pipelines, dynamic_state, self.bound);
+        for id in changed {
+            let local = locals.get(&id).cloned();
+
+            if let Some(NodeKind::Button(button)) = self.scene.get_mut(id).map(|n| &mut n.kind) {
+                if button.hover(local) {
+                    self.dirty = true;
+                }
+            }
         }
+    }
+
+    // Clicks the topmost visible button under v
+    pub fn click(&mut self, v: Vector) -> Result<()> {
+        for id in self.scene.hits(v) {
+            let local = match self.scene.world_transform(id).inverse() {
+                Some(inverse) => inverse.apply(v),
+                None => continue,
+            };
 
-        for button in self.buttons.iter_mut() {
-            button.shape.draw(device.clone(), builder, &pipelines, dynamic_state, self.bound);
+            if let Some(NodeKind::Button(button)) = self.scene.get_mut(id).map(|n| &mut n.kind) {
+                return button.clicked(local);
+            }
         }
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    // Turns the widget dirty once the frame requested by request_frame is due
+    pub fn tick(&mut self, now: Instant) {
+        match self.redraw_at {
+            Some(t) if t <= now => {
+                self.redraw_at = None;
+                self.dirty = true;
+            },
+            _ => (),
+        };
+    }
+
+    pub fn draw(&mut self, renderer: &mut dyn Renderer) -> Result<()> {
+        let mut batcher = Batcher::new(renderer, &mut self.batches);
+        let renderer = &mut batcher;
+
+        renderer.begin(self.color)?;
+
+        match &self.gradient {
+            Some(g) => {
+                let vertices = [
+                    Vector::new(0f32, 0f32),
+                    Vector::new(self.bound.x(), 0f32),
+                    Vector::new(0f32, self.bound.y()),
+                    self.bound,
+                ];
+
+                let span = g.span(vertices[0], self.bound);
+                renderer.gradient(&mut self.gradient_buffer, &vertices, &[0u32, 1u32, 2u32, 1u32, 3u32, 2u32], g, span)?;
+            },
+            None => (),
+        };
+
+        self.scene.draw(renderer)?;
+
+        renderer.end()
+    }
+}
+
+// Adds the shapes, then the tools, then the groups of a config level below parent
+fn build_children(
+    scene: &mut Scene,
+    parent: NodeId,
+    shapes: &Option<Vec<ShapeWidgetConfig>>,
+    tools: &Option<Vec<ToolWidgetConfig>>,
+    groups: &Option<Vec<GroupConfig>>,
+    bound: Vector,
+    root: &std::path::Path,
+    fonts: &mut HashMap<PathBuf, Font<'static>>,
+) -> Result<()> {
+    for s in shapes.iter().flatten() {
+        let shape = build_node_shape(s, root, fonts)?;
+        let transform = build_transform(&s.transform, Some(shape.center()));
+
+        let layer = build_layer(scene, &s.layer)?;
+        let id = scene.add(parent, NodeKind::Shape(shape))?;
+
+        let node = scene.get_mut(id).unwrap();
+        node.name = s.name.clone();
+        node.visible = s.visible.unwrap_or(true);
+        node.transform = transform;
+        node.z = s.z.unwrap_or(0);
+        node.layer = layer;
+        node.blend = build_blend(s.blend);
+        node.backdrop = s.backdrop_blur.unwrap_or(0f32).max(0f32);
+    }
+
+    for t in tools.iter().flatten() {
+        let button = build_button(t)?;
+        let transform = build_transform(&t.transform, Some(button.shape.center()));
+
+        let layer = build_layer(scene, &t.layer)?;
+        let id = scene.add(parent, NodeKind::Button(button))?;
+
+        let node = scene.get_mut(id).unwrap();
+        node.name = t.name.clone();
+        node.visible = t.visible.unwrap_or(true);
+        node.transform = transform;
+        node.z = t.z.unwrap_or(0);
+        node.layer = layer;
+        node.blend = build_blend(t.blend);
+    }
+
+    for g in groups.iter().flatten() {
+        let id = scene.add(parent, NodeKind::Group)?;
+
+        build_children(scene, id, &g.shape, &g.tool, &g.group, bound, root, fonts)?;
+
+        // The center of a group is only known once everything in it is built
+        let bounds = scene.bounds(id);
+        let center = bounds.map(|(min, max)| (min + max) / 2f32);
+        let transform = build_transform(&g.transform, center);
+        let layer = build_layer(scene, &g.layer)?;
+
+        let clip = match &g.clip {
+            Some(c) => Some(Clip::new(&mut *build_node_shape(c, root, fonts)?, bound)?),
+            None => None,
+        };
+
+        let mask = match &g.mask {
+            Some(m) => Some(build_mask(m, bounds, root)?),
+            None => None,
+        };
+
+        let node = scene.get_mut(id).unwrap();
+        node.name = g.name.clone();
+        node.visible = g.visible.unwrap_or(true);
+        node.transform = transform;
+        node.z = g.z.unwrap_or(0);
+        node.layer = layer;
+        node.clip = clip;
+        node.mask = mask;
+        node.opacity = g.opacity.unwrap_or(1f32).max(0f32).min(1f32);
+    }
+
+    Ok(())
+}
+
+// Whether any shape, those in groups included, blurs its backdrop
+fn uses_backdrop(shapes: &Option<Vec<ShapeWidgetConfig>>, groups: &Option<Vec<GroupConfig>>) -> bool {
+    shapes.iter().flatten().any(|s| s.backdrop_blur.unwrap_or(0f32) > 0f32)
+        || groups.iter().flatten().any(|g| uses_backdrop(&g.shape, &g.group))
+}
+
+fn build_blend(blend: Option<Blend>) -> BlendMode {
+    match blend.unwrap_or(Blend::Normal) {
+        Blend::Normal => BlendMode::Normal,
+        Blend::Additive => BlendMode::Additive,
+        Blend::Multiply => BlendMode::Multiply,
+        Blend::Screen => BlendMode::Screen,
+    }
+}
+
+// Loads the image of a mask, without a rect it covers everything in the group
+fn build_mask(m: &MaskConfig, bounds: Option<(Vector, Vector)>, root: &std::path::Path) -> Result<Mask> {
+    let image = Frame::load(root.join(&m.image))?;
+
+    let mode = match m.mode {
+        Some(MaskModeConfig::Luminance) => MaskMode::Luminance,
+        Some(MaskModeConfig::Alpha) | None => MaskMode::Alpha,
+    };
+
+    let (position, size) = match (m.rect, bounds) {
+        (Some(r), _) => (Vector::new(r[0], r[1]), Vector::new(r[2], r[3])),
+        (None, Some((min, max))) => (min, max - min),
+        (None, None) => (Vector::default(), Vector::default()),
+    };
+
+    Ok(Mask::new(&image, mode, position, size))
+}
+
+// Looks the layer named in the config up, None leaves the node on the layer of its parent
+fn build_layer(scene: &Scene, layer: &Option<String>) -> Result<Option<usize>> {
+    match layer {
+        Some(name) => Ok(Some(scene.layer(name)?)),
+        None => Ok(None),
+    }
+}
+
+// Combines the parts of a TransformConfig, rotate, skew and scale happen around the origin
+// before the whole is moved by offset. center is what origin = "center" refers to.
+fn build_transform(config: &TransformConfig, center: Option<Vector>) -> Transform {
+    let offset = config.offset.unwrap_or([0f32, 0f32]);
+    let scale = config.scale.unwrap_or([1f32, 1f32]);
+    let skew = config.skew.unwrap_or([0f32, 0f32]);
+
+    let origin = match config.origin {
+        Some(Origin::Point(p)) => Vector::new(p[0], p[1]),
+        Some(Origin::Anchor(Anchor::Center)) => center.unwrap_or_default(),
+        None => Vector::default(),
+    };
+
+    Transform::translate(Vector::new(offset[0], offset[1]) + origin)
+        * Transform::rotate(config.rotate.unwrap_or(0f32).to_radians())
+        * Transform::skew(Vector::new(skew[0].to_radians(), skew[1].to_radians()))
+        * Transform::scale(Vector::new(scale[0], scale[1]))
+        * Transform::translate(Vector::default() - origin)
+}
+
+// Creates a shape from its config, with its format, gradient and effects applied
+fn build_node_shape(s: &ShapeWidgetConfig, root: &std::path::Path, fonts: &mut HashMap<PathBuf, Font<'static>>) -> Result<Box<dyn Shape>> {
+    let mut shape = match s.shape_type {
+        Shapes::Text => build_text(s, root, fonts)?,
+        Shapes::Image => build_image(s, root)?,
+        _ => build_shape(s.shape_type, &s.shape, &s.path, &s.color, s.radius)?,
+    };
+    shape.format(build_format(s.shape_type, &s.format, &s.color)?);
+
+    match &s.gradient {
+        Some(g) => shape.gradient(build_gradient(g)?),
+        None => (),
+    };
+
+    if s.shadow.is_some() || s.blur.is_some() {
+        let shadow = match &s.shadow {
+            Some(sh) => Some(build_shadow(sh)?),
+            None => None,
+        };
+
+        shape = Box::new(Effects::new(shape, shadow, s.blur.unwrap_or(0f32)));
+    }
+
+    Ok(shape)
+}
+
+fn build_button(t: &ToolWidgetConfig) -> Result<Button> {
+    match t.ty {
+        Tools::Button => {
+            let shape_type = t.shape_type.unwrap_or(Shapes::Rectangle);
+            let mut shape = build_shape(shape_type, &t.shape, &t.path, &t.color, t.radius)?;
+            shape.format(build_format(shape_type, &t.format, &t.color)?);
+
+            match &t.gradient {
+                Some(g) => shape.gradient(build_gradient(g)?),
+                None => (),
+            };
+
+            let action = match &t.action {
+                Some(a) => match a.ty {
+                    Type::Clicked => Some(Action::new(a.action.clone(), ActionType::Clicked)),
+                },
+                None => None,
+            };
+
+            let mut button = Button::new(shape, action);
+
+            match &t.color {
+                Some(c) => button.color = Color::parse(c)?,
+                None => (),
+            };
+
+            match &t.hover_color {
+                Some(c) => button.hover_color = Some(Color::parse(c)?),
+                None => (),
+            };
+
+            Ok(button)
+        },
+    }
+}
+
+// Creates the shape described by a shape_type and its list of values from the config
+fn build_shape(
+    shape_type: Shapes,
+    values: &[f32],
+    path: &Option<String>,
+    color: &Option<String>,
+    radius: Option<Radius>,
+) -> Result<Box<dyn Shape>> {
+    let mut shape: Box<dyn Shape> = match shape_type {
+        Shapes::Triangle => {
+            if values.len() != 6 {
+                return Err(CoreError::InvalidShapeFormat);
+            };
+
+            Box::new(Triangle::new(
+                Vector::new(values[0], values[1]),
+                Vector::new(values[2], values[3]),
+                Vector::new(values[4], values[5]),
+            ))
+        },
+        Shapes::Rectangle => {
+            if values.len() != 4 {
+                return Err(CoreError::InvalidShapeFormat);
+            };
+
+            let mut rectangle = Rectangle::new(values[0], values[1], values[2], values[3]);
+
+            match radius {
+                Some(r) => rectangle.radius(r.corners()),
+                None => (),
+            };
+
+            Box::new(rectangle)
+        },
+        Shapes::Circle => {
+            if values.len() != 3 {
+                return Err(CoreError::InvalidShapeFormat);
+            };
+
+            Box::new(Ellipse::circle(Vector::new(values[0], values[1]), values[2]))
+        },
+        Shapes::Ellipse => {
+            if values.len() != 4 {
+                return Err(CoreError::InvalidShapeFormat);
+            };
+
+            Box::new(Ellipse::new(
+                Vector::new(values[0], values[1]),
+                Vector::new(values[2], values[3]),
+            ))
+        },
+        Shapes::Polygon => {
+            if values.len() < 6 || values.len() % 2 != 0 {
+                return Err(CoreError::InvalidShapeFormat);
+            };
+
+            Box::new(Polygon::new(points(values)))
+        },
+        Shapes::Polyline => {
+            if values.len() < 4 || values.len() % 2 != 0 {
+                return Err(CoreError::InvalidShapeFormat);
+            };
+
+            Box::new(Polyline::new(points(values)))
+        },
+        Shapes::Path => {
+            let d = match path {
+                Some(d) => d,
+                None => return Err(CoreError::InvalidShapeFormat),
+            };
+
+            let (position, scale) = match values.len() {
+                0 => (Vector::default(), 1f32),
+                2 => (Vector::new(values[0], values[1]), 1f32),
+                3 => (Vector::new(values[0], values[1]), values[2]),
+                _ => return Err(CoreError::InvalidShapeFormat),
+            };
+
+            Box::new(Path::parse(d, position, scale)?)
+        },
+        // Text and Image need more settings, they are built by build_text and build_image instead
+        Shapes::Text | Shapes::Image => return Err(CoreError::InvalidShapeFormat),
+    };
+
+    match color {
+        Some(c) => shape.color(Color::parse(c)?),
+        None => (),
+    };
+
+    Ok(shape)
+}
+
+// Lays out a Text shape, loading its font relative to the widget TOML
+fn build_text(config: &ShapeWidgetConfig, root: &std::path::Path, fonts: &mut HashMap<PathBuf, Font<'static>>) -> Result<Box<dyn Shape>> {
+    if config.shape.len() != 2 {
+        return Err(CoreError::InvalidShapeFormat);
+    };
+
+    let (string, font_path) = match (&config.text, &config.font) {
+        (Some(t), Some(f)) => (t, root.join(f)),
+        _ => return Err(CoreError::InvalidShapeFormat),
+    };
+
+    if !fonts.contains_key(&font_path) {
+        let font = text::load_font(&font_path)?;
+        fonts.insert(font_path.clone(), font);
+    }
+
+    let align = match config.align.unwrap_or(Align::Left) {
+        Align::Left => TextAlign::Left,
+        Align::Center => TextAlign::Center,
+        Align::Right => TextAlign::Right,
+    };
+
+    let layout = text::layout(
+        &fonts[&font_path],
+        string,
+        Vector::new(config.shape[0], config.shape[1]),
+        config.font_size.unwrap_or(16f32),
+        config.wrap,
+        align,
+    );
+
+    let mut shape = Text::new(layout);
+
+    match &config.color {
+        Some(c) => shape.color(Color::parse(c)?),
+        None => (),
+    };
+
+    Ok(Box::new(shape))
+}
+
+// Loads the picture of an Image shape relative to the widget TOML
+fn build_image(config: &ShapeWidgetConfig, root: &std::path::Path) -> Result<Box<dyn Shape>> {
+    if config.shape.len() != 4 {
+        return Err(CoreError::InvalidShapeFormat);
+    };
+
+    let frame = match &config.image {
+        Some(i) => Frame::load(root.join(i))?,
+        None => return Err(CoreError::InvalidShapeFormat),
+    };
+
+    let mode = match config.scaling.unwrap_or(Scaling::Fit) {
+        Scaling::Fit => ImageMode::Fit,
+        Scaling::Fill => ImageMode::Fill,
+        Scaling::Stretch => ImageMode::Stretch,
+        Scaling::Tile => ImageMode::Tile,
+    };
+
+    let mut image = Image::new(
+        Vector::new(config.shape[0], config.shape[1]),
+        Vector::new(config.shape[2], config.shape[3]),
+        frame,
+        mode,
+    );
+
+    image.opacity = config.opacity.unwrap_or(1f32);
+
+    Ok(Box::new(image))
+}
+
+fn build_gradient(config: &GradientConfig) -> Result<Gradient> {
+    let last = config.stops.len().saturating_sub(1).max(1) as f32;
+
+    let mut stops = Vec::with_capacity(config.stops.len());
+
+    for (i, s) in config.stops.iter().enumerate() {
+        let offset = s.offset.unwrap_or(i as f32 / last);
+
+        stops.push((offset, Color::parse(&s.color)?));
+    }
+
+    let kind = match config.ty {
+        GradientType::Linear => GradientKind::Linear {
+            angle: config.angle.unwrap_or(0f32),
+        },
+        GradientType::Radial => {
+            let c = config.center.unwrap_or([0.5f32, 0.5f32]);
+
+            GradientKind::Radial {
+                center: Vector::new(c[0], c[1]),
+                radius: config.radius.unwrap_or(1f32),
+            }
+        },
+    };
+
+    Gradient::new(kind, stops)
+}
+
+fn build_shadow(config: &ShadowConfig) -> Result<Shadow> {
+    let offset = config.offset.unwrap_or([0f32, 0f32]);
+
+    let color = match &config.color {
+        Some(c) => Color::parse(c)?,
+        None => Color::new(0u8, 0u8, 0u8, 128u8),
+    };
+
+    Ok(Shadow {
+        offset: Vector::new(offset[0], offset[1]),
+        blur: config.blur.unwrap_or(4f32),
+        color,
+    })
+}
+
+// Pairs up a flat list of coordinates into points
+fn points(values: &[f32]) -> Vec<Vector> {
+    values
+        .chunks(2)
+        .map(|c| Vector::new(c[0], c[1]))
+        .collect()
+}
+
+// Creates the ShapeFormat, including the outline stroke, of a shape from the config
+fn build_format(shape_type: Shapes, config: &FormatConfig, color: &Option<String>) -> Result<ShapeFormat> {
+    let format = config.format.unwrap_or(Format::Fill);
+
+    let color = match config.line_color.as_ref().or(color.as_ref()) {
+        Some(c) => Color::parse(c)?,
+        None => Color::new(0, 0, 0, u8::MAX),
+    };
+
+    let mut stroke = Stroke::new(config.line_width.unwrap_or(1f32), color);
+
+    // A polyline has no inside to fill, so it is a line whatever the format says
+    let format = match shape_type {
+        Shapes::Polyline => Format::Line,
+        _ => format,
+    };
+
+    stroke.join = match config.line_join.unwrap_or(Join::Miter) {
+        Join::Miter => LineJoin::Miter,
+        Join::Round => LineJoin::Round,
+        Join::Bevel => LineJoin::Bevel,
+    };
+
+    stroke.cap = match config.line_cap.unwrap_or(Cap::Butt) {
+        Cap::Butt => LineCap::Butt,
+        Cap::Round => LineCap::Round,
+        Cap::Square => LineCap::Square,
+    };
+
+    Ok(match format {
+        Format::Fill => ShapeFormat::Fill,
+        Format::Line => ShapeFormat::Line(stroke),
+        Format::FillLine => ShapeFormat::FillLine(stroke),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outlines_default_to_the_shape_color_or_black() {
+        let line = FormatConfig {
+            format: Some(Format::Line),
+            ..FormatConfig::default()
+        };
+
+        let black = Stroke::new(1f32, Color::new(0, 0, 0, u8::MAX));
+        assert_eq!(build_format(Shapes::Rectangle, &line, &None).unwrap(), ShapeFormat::Line(black));
+
+        let red = Stroke::new(1f32, Color::new(255, 0, 0, u8::MAX));
+        assert_eq!(build_format(Shapes::Rectangle, &line, &Some("red".to_string())).unwrap(), ShapeFormat::Line(red));
+        assert_eq!(build_format(Shapes::Polyline, &FormatConfig::default(), &Some("red".to_string())).unwrap(), ShapeFormat::Line(red));
+    }
+
+    #[test]
+    fn rejects_shadows_too_large_for_the_widget() {
+        let config: WidgetConfig = toml::from_str(r#"
+            width = 60
+            height = 200
+            position = [0, 0]
+            shadow = { offset = [0, 4], blur = 26 }
+        "#).unwrap();
+
+        match Widget::new(config) {
+            Err(CoreError::WidgetTooSmall(inset)) => assert_eq!(inset, 30f32),
+            r => panic!("{:?}", r.map(|_| ())),
+        }
+    }
+    #[test]
+    fn rejects_gradients_without_stops() {
+        let config: GradientConfig = toml::from_str(r#"
+            ty = "Linear"
+            stops = []
+        "#).unwrap();
+
+        match build_gradient(&config) {
+            Err(CoreError::InvalidGradient(_)) => (),
+            r => panic!("{:?}", r),
+        }
+    }
+}
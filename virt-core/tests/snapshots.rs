@@ -0,0 +1,31 @@
+use virt_core::headless::HeadlessState;
+use virt_core::render::Backend;
+use virt_core::snapshot::{Snapshots, SnapshotStatus};
+
+use std::path::Path;
+
+// The references are blessed with the software backend, which draws the same on every machine
+#[test]
+fn fixtures_match_their_references() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+    let headless = HeadlessState::new(Backend::Software).unwrap();
+
+    let results = Snapshots::new(&root).run(&headless).unwrap();
+
+    let failed: Vec<String> = results
+        .iter()
+        .filter_map(|r| match &r.status {
+            SnapshotStatus::Passed => None,
+            SnapshotStatus::Missing => Some(format!("{} has no reference", r.config.display())),
+            SnapshotStatus::Failed(c) => Some(format!(
+                "{} ({} pixels differ, max delta {})",
+                r.config.display(),
+                c.mismatched,
+                c.max_delta,
+            )),
+        })
+        .collect();
+
+    assert!(!results.is_empty());
+    assert!(failed.is_empty(), "snapshots failed:\n{}", failed.join("\n"));
+}